@@ -1,35 +1,151 @@
 use std::fs;
+use std::collections::{HashMap, HashSet};
+use num_bigint::BigUint;
+use aoc_common::cli::Args;
 
-fn transform_string(string: &String, reps: usize) -> usize {
-  let mut curr_string = string.clone();
+const PREFIX_CAP: usize = 16;
+const MAX_ATOMS: usize = 4096;
 
+fn look_and_say(digits: &[u8]) -> Vec<u8> {
+  let mut next = Vec::with_capacity(digits.len() * 2);
+  let mut index = 0;
+  while index < digits.len() {
+    let curr_digit = digits[index];
+    let mut digit_count: usize = 1;
+    while index + 1 < digits.len() && digits[index + 1] == curr_digit {
+      index += 1;
+      digit_count += 1;
+    }
+    next.extend(digit_count.to_string().bytes());
+    next.push(curr_digit);
+    index += 1;
+  }
+  return next;
+}
+
+fn transform_string(string: &str, reps: usize) -> usize {
+  let mut curr = string.as_bytes().to_vec();
   for _ in 0..reps {
-    let mut next_string = "".to_string();
-    let curr_string_vec = curr_string.chars().collect::<Vec<char>>();
-    let mut index = 0;
-    while index < curr_string_vec.len() {
-      let curr_char = curr_string_vec[index];
-      let mut char_count: u32 = 1;
-
-      while index + 1 < curr_string_vec.len() && curr_string_vec[index + 1] == curr_char {
-        index += 1;
-        char_count += 1;
-      }
+    curr = look_and_say(&curr);
+  }
+  return curr.len();
+}
 
-      next_string.push(char::from_digit(char_count, 10).unwrap());
-      next_string.push(curr_char);
-      index += 1;
+/*
+  Look-and-say never changes the last digit of a string, so L.R evolves as two
+  independent halves for ever exactly when the last digit of L never equals the
+  first digit of any descendant of R. Only a short prefix of R decides that
+  first digit, so we follow a capped prefix (dropping its last run once it may
+  be incomplete) until it starts repeating.
+*/
+fn splits_forever(last: u8, right: &[u8]) -> bool {
+  let mut truncated = right.len() > PREFIX_CAP;
+  let mut prefix = right[..right.len().min(PREFIX_CAP)].to_vec();
+  let mut seen: HashSet<(Vec<u8>, bool)> = HashSet::new();
+
+  loop {
+    if prefix[0] == last { return false; }
+    if !seen.insert((prefix.clone(), truncated)) { return true; }
+
+    let mut complete = prefix.len();
+    if truncated {
+      while complete > 0 && prefix[complete - 1] == prefix[prefix.len() - 1] { complete -= 1; }
+      if complete == 0 { return false; }
     }
 
-    curr_string = next_string;
+    let next = look_and_say(&prefix[..complete]);
+    truncated = truncated || next.len() > PREFIX_CAP;
+    prefix = next[..next.len().min(PREFIX_CAP)].to_vec();
   }
+}
 
-  return curr_string.len();
+fn split_into_atoms(digits: &[u8]) -> Vec<&[u8]> {
+  let mut atoms = Vec::new();
+  let mut start = 0;
+  for i in 1..digits.len() {
+    if digits[i - 1] != digits[i] && splits_forever(digits[i - 1], &digits[i..]) {
+      atoms.push(&digits[start..i]);
+      start = i;
+    }
+  }
+  atoms.push(&digits[start..]);
+  return atoms;
+}
+
+/*
+  Atoms are discovered as they appear rather than read from Conway's table:
+  once a seed has decayed, the atoms found are exactly his 92 common elements
+  (plus the transuranic ones for digits above 3). Each atom's row lists the
+  atoms its single-step descendant splits into.
+*/
+struct Chemistry {
+  atoms: Vec<Vec<u8>>,
+  index: HashMap<Vec<u8>, usize>,
+  decays_into: Vec<Vec<usize>>
+}
+
+impl Chemistry {
+  fn new() -> Self {
+    return Chemistry{atoms: Vec::new(), index: HashMap::new(), decays_into: Vec::new()};
+  }
+
+  fn atom_id(&mut self, atom: &[u8]) -> usize {
+    if let Some(id) = self.index.get(atom) { return *id; }
+    self.atoms.push(atom.to_vec());
+    self.index.insert(atom.to_vec(), self.atoms.len() - 1);
+    return self.atoms.len() - 1;
+  }
+
+  fn decompose(&mut self, digits: &[u8]) -> Option<Vec<usize>> {
+    let mut ids = split_into_atoms(digits).iter().map(|atom| self.atom_id(atom)).collect::<Vec<usize>>();
+    let mut next = self.decays_into.len();
+    while next < self.atoms.len() {
+      if self.atoms.len() > MAX_ATOMS { return None; }
+      let descendant = look_and_say(&self.atoms[next]);
+      let products = split_into_atoms(&descendant).iter().map(|atom| self.atom_id(atom)).collect();
+      self.decays_into.push(products);
+      next += 1;
+    }
+    ids.sort();
+    return Some(ids);
+  }
+}
+
+fn count_length_after(seed: &str, reps: usize) -> BigUint {
+  let mut chemistry = Chemistry::new();
+  let seed_atoms = match chemistry.decompose(seed.as_bytes()) {
+    Some(seed_atoms) => seed_atoms,
+    None => return BigUint::from(transform_string(seed, reps))
+  };
+
+  let mut counts = vec![BigUint::ZERO; chemistry.atoms.len()];
+  for atom in seed_atoms.iter() { counts[*atom] += 1_u32; }
+
+  for _ in 0..reps {
+    let mut next_counts = vec![BigUint::ZERO; chemistry.atoms.len()];
+    for (atom, count) in counts.iter().enumerate() {
+      if *count == BigUint::ZERO { continue; }
+      for product in chemistry.decays_into[atom].iter() {
+        next_counts[*product] += count;
+      }
+    }
+    counts = next_counts;
+  }
+
+  return counts.iter().enumerate()
+    .map(|(atom, count)| count * chemistry.atoms[atom].len())
+    .sum();
 }
 
 fn main() {
-  let string = fs::read_to_string("./data/q10.txt")
+  let args = Args::parse();
+  let contents = fs::read_to_string("./data/q10.txt")
     .expect("Should have been able to read file");
-  println!("Part 1: {}", transform_string(&string, 40));
-  println!("Part 2: {}", transform_string(&string, 50));
-}
\ No newline at end of file
+  let seed = contents.trim();
+  println!("Part 1: {}", count_length_after(seed, 40));
+  println!("Part 2: {}", count_length_after(seed, 50));
+  if let Some(steps) = args.value("--steps") {
+    let steps = steps.parse::<usize>().unwrap();
+    println!("After {} steps: {}", steps, count_length_after(seed, steps));
+  }
+}
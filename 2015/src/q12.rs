@@ -1,82 +1,340 @@
 use std::fs;
-use std::collections::HashMap;
+use std::fmt;
+use aoc_common::cli::Args;
 
-#[derive(Debug, PartialEq)]
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
 enum JSONData {
-  JSONArray(Vec<JSONData>),
-  JSONObject(HashMap<String, JSONData>),
+  JSONNull,
+  JSONBool(bool),
+  JSONNumber(f64),
   JSONString(String),
-  JSONNumber(i32),
-}
-
-fn parse_key(string: &Vec<char>, index: usize) -> (usize, String) {
-  let mut curr_index = index;
-  let mut str = String::new();
-  curr_index += 1;
-  while string[curr_index] != '\"' {
-    str.push(string[curr_index]);
-    curr_index += 1;
-  }
-  return (curr_index + 1, str);
-}
-
-fn parse_json(string: &Vec<char>, index: usize) -> (usize, JSONData) {
-  let mut curr_index = index;
-  match string[curr_index] {
-    '{' => {
-      let mut key_values: HashMap<String, JSONData> = HashMap::new();
-      curr_index += 1;
-      while string[curr_index] != '}' {
-        let (next_index, key) = parse_key(string, curr_index);
-        curr_index = next_index + 1;
-        let (after_index, value) = parse_json(string, curr_index);
-        key_values.insert(key, value);
-        curr_index = after_index;
-        if string[curr_index] == ',' { curr_index += 1 }
+  JSONArray(Vec<JSONData>),
+  JSONObject(Vec<(String, JSONData)>)
+}
+
+#[derive(Debug, PartialEq)]
+struct ParseError {
+  offset: usize,
+  message: String
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} at byte {}", self.message, self.offset)
+  }
+}
+
+struct Parser<'a> {
+  bytes: &'a [u8],
+  index: usize,
+  depth: usize
+}
+
+impl<'a> Parser<'a> {
+  fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+    return Err(ParseError{offset: self.index, message: message.to_string()});
+  }
+
+  fn peek(&self) -> Option<u8> {
+    return self.bytes.get(self.index).copied();
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() { self.index += 1; }
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+    if self.peek() != Some(byte) { return self.error(&format!("expected '{}'", byte as char)); }
+    self.index += 1;
+    return Ok(());
+  }
+
+  fn parse_document(&mut self) -> Result<JSONData, ParseError> {
+    self.skip_whitespace();
+    let value = self.parse_value()?;
+    self.skip_whitespace();
+    if self.index != self.bytes.len() { return self.error("trailing characters after JSON value"); }
+    return Ok(value);
+  }
+
+  fn parse_value(&mut self) -> Result<JSONData, ParseError> {
+    return match self.peek() {
+      Some(b'{') => self.parse_object(),
+      Some(b'[') => self.parse_array(),
+      Some(b'"') => Ok(JSONData::JSONString(self.parse_string()?)),
+      Some(b't') => self.parse_literal("true", JSONData::JSONBool(true)),
+      Some(b'f') => self.parse_literal("false", JSONData::JSONBool(false)),
+      Some(b'n') => self.parse_literal("null", JSONData::JSONNull),
+      Some(b'-' | b'0'..=b'9') => self.parse_number(),
+      Some(_) => self.error("expected a JSON value"),
+      None => self.error("unexpected end of input")
+    };
+  }
+
+  fn parse_literal(&mut self, literal: &str, value: JSONData) -> Result<JSONData, ParseError> {
+    if !self.bytes[self.index..].starts_with(literal.as_bytes()) {
+      return self.error(&format!("expected '{}'", literal));
+    }
+    self.index += literal.len();
+    return Ok(value);
+  }
+
+  fn enter(&mut self) -> Result<(), ParseError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH { return self.error("nesting too deep"); }
+    self.index += 1;
+    self.skip_whitespace();
+    return Ok(());
+  }
+
+  fn parse_object(&mut self) -> Result<JSONData, ParseError> {
+    self.enter()?;
+    let mut key_values: Vec<(String, JSONData)> = Vec::new();
+    if self.peek() == Some(b'}') {
+      self.index += 1;
+      self.depth -= 1;
+      return Ok(JSONData::JSONObject(key_values));
+    }
+
+    loop {
+      if self.peek() != Some(b'"') { return self.error("expected a string key"); }
+      let key = self.parse_string()?;
+      self.skip_whitespace();
+      self.expect(b':')?;
+      self.skip_whitespace();
+      key_values.push((key, self.parse_value()?));
+      self.skip_whitespace();
+      match self.peek() {
+        Some(b',') => {
+          self.index += 1;
+          self.skip_whitespace();
+        },
+        Some(b'}') => break,
+        _ => return self.error("expected ',' or '}'")
       }
-      return (curr_index + 1, JSONData::JSONObject(key_values));
-    },
+    }
+
+    self.index += 1;
+    self.depth -= 1;
+    return Ok(JSONData::JSONObject(key_values));
+  }
 
-    '[' => {
-      let mut values: Vec<JSONData> = Vec::new();
-      curr_index += 1;
-      while string[curr_index] != ']' {
-        let (next_index, value) = parse_json(string, curr_index);
-        values.push(value);
-        curr_index = next_index;
-        if string[curr_index] == ',' { curr_index += 1 }
+  fn parse_array(&mut self) -> Result<JSONData, ParseError> {
+    self.enter()?;
+    let mut values: Vec<JSONData> = Vec::new();
+    if self.peek() == Some(b']') {
+      self.index += 1;
+      self.depth -= 1;
+      return Ok(JSONData::JSONArray(values));
+    }
+
+    loop {
+      values.push(self.parse_value()?);
+      self.skip_whitespace();
+      match self.peek() {
+        Some(b',') => {
+          self.index += 1;
+          self.skip_whitespace();
+        },
+        Some(b']') => break,
+        _ => return self.error("expected ',' or ']'")
       }
-      return (curr_index + 1, JSONData::JSONArray(values));
-    },
+    }
+
+    self.index += 1;
+    self.depth -= 1;
+    return Ok(JSONData::JSONArray(values));
+  }
+
+  fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+    let digits = match self.bytes.get(self.index..self.index + 4) {
+      Some(digits) => digits,
+      None => return self.error("truncated \\u escape")
+    };
+    if !digits.iter().all(|digit| digit.is_ascii_hexdigit()) { return self.error("invalid \\u escape"); }
+    let value = u32::from_str_radix(std::str::from_utf8(digits).unwrap(), 16).unwrap();
+    self.index += 4;
+    return Ok(value);
+  }
 
-    '\"' => {
-      let mut json_string = String::new();
-      curr_index += 1;
-      while string[curr_index] != '\"' {
-        json_string.push(string[curr_index]);
-        curr_index += 1;
+  fn parse_string(&mut self) -> Result<String, ParseError> {
+    self.index += 1;
+    let mut json_string = String::new();
+    loop {
+      let start = self.index;
+      while let Some(byte) = self.peek() {
+        if byte == b'"' || byte == b'\\' || byte < 0x20 { break; }
+        self.index += 1;
       }
-      return (curr_index + 1, JSONData::JSONString(json_string));
+      json_string.push_str(std::str::from_utf8(&self.bytes[start..self.index]).unwrap());
+
+      match self.peek() {
+        Some(b'"') => {
+          self.index += 1;
+          return Ok(json_string);
+        },
+        Some(b'\\') => {
+          self.index += 1;
+          let escape = match self.peek() {
+            Some(escape) => escape,
+            None => return self.error("unterminated string")
+          };
+          self.index += 1;
+          match escape {
+            b'"' => json_string.push('"'),
+            b'\\' => json_string.push('\\'),
+            b'/' => json_string.push('/'),
+            b'b' => json_string.push('\u{8}'),
+            b'f' => json_string.push('\u{c}'),
+            b'n' => json_string.push('\n'),
+            b'r' => json_string.push('\r'),
+            b't' => json_string.push('\t'),
+            b'u' => json_string.push(self.parse_unicode_escape()?),
+            _ => {
+              self.index -= 1;
+              return self.error("invalid escape");
+            }
+          }
+        },
+        Some(_) => return self.error("unescaped control character in string"),
+        None => return self.error("unterminated string")
+      }
+    }
+  }
+
+  fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+    let high = self.parse_hex4()?;
+    let code_point = if (0xd800..0xdc00).contains(&high) {
+      if !self.bytes[self.index..].starts_with(b"\\u") { return self.error("unpaired surrogate"); }
+      self.index += 2;
+      let low = self.parse_hex4()?;
+      if !(0xdc00..0xe000).contains(&low) { return self.error("unpaired surrogate"); }
+      0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+    } else {
+      high
+    };
+    return match char::from_u32(code_point) {
+      Some(c) => Ok(c),
+      None => self.error("unpaired surrogate")
+    };
+  }
+
+  fn parse_digits(&mut self) -> usize {
+    let start = self.index;
+    while let Some(b'0'..=b'9') = self.peek() { self.index += 1; }
+    return self.index - start;
+  }
+
+  fn parse_number(&mut self) -> Result<JSONData, ParseError> {
+    let start = self.index;
+    if self.peek() == Some(b'-') { self.index += 1; }
+    match self.peek() {
+      Some(b'0') => self.index += 1,
+      Some(b'1'..=b'9') => { self.parse_digits(); },
+      _ => return self.error("expected a digit")
+    }
+    if self.peek() == Some(b'.') {
+      self.index += 1;
+      if self.parse_digits() == 0 { return self.error("expected a digit after '.'"); }
+    }
+    if let Some(b'e' | b'E') = self.peek() {
+      self.index += 1;
+      if let Some(b'+' | b'-') = self.peek() { self.index += 1; }
+      if self.parse_digits() == 0 { return self.error("expected a digit in exponent"); }
     }
 
-    _ => {
-      let mut json_value = String::new();
-      while (string[curr_index] == '-') || (string[curr_index] >= '0' && string[curr_index] <= '9') {
-        json_value.push(string[curr_index]);
-        curr_index += 1;
+    let value = std::str::from_utf8(&self.bytes[start..self.index]).unwrap().parse::<f64>().unwrap();
+    if !value.is_finite() {
+      self.index = start;
+      return self.error("number out of range");
+    }
+    return Ok(JSONData::JSONNumber(value));
+  }
+}
+
+fn parse_json(document: &str) -> Result<JSONData, ParseError> {
+  return Parser{bytes: document.as_bytes(), index: 0, depth: 0}.parse_document();
+}
+
+fn write_string(out: &mut String, string: &str) {
+  out.push('"');
+  for c in string.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      '\u{8}' => out.push_str("\\b"),
+      '\u{c}' => out.push_str("\\f"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c)
+    }
+  }
+  out.push('"');
+}
+
+impl JSONData {
+  fn write(&self, out: &mut String, indent: Option<usize>, level: usize) {
+    let newline = |out: &mut String, level: usize| if let Some(width) = indent {
+      out.push('\n');
+      out.push_str(&" ".repeat(width * level));
+    };
+
+    match self {
+      JSONData::JSONNull => out.push_str("null"),
+      JSONData::JSONBool(val) => out.push_str(if *val { "true" } else { "false" }),
+      JSONData::JSONNumber(val) => out.push_str(&val.to_string()),
+      JSONData::JSONString(val) => write_string(out, val),
+      JSONData::JSONArray(values) => {
+        out.push('[');
+        for (i, value) in values.iter().enumerate() {
+          if i > 0 { out.push(','); }
+          newline(out, level + 1);
+          value.write(out, indent, level + 1);
+        }
+        if !values.is_empty() { newline(out, level); }
+        out.push(']');
+      },
+      JSONData::JSONObject(key_values) => {
+        out.push('{');
+        for (i, (key, value)) in key_values.iter().enumerate() {
+          if i > 0 { out.push(','); }
+          newline(out, level + 1);
+          write_string(out, key);
+          out.push_str(if indent.is_some() { ": " } else { ":" });
+          value.write(out, indent, level + 1);
+        }
+        if !key_values.is_empty() { newline(out, level); }
+        out.push('}');
       }
-      return (curr_index, JSONData::JSONNumber(json_value.parse::<i32>().unwrap()));
     }
   }
+
+  fn to_pretty(&self, indent: usize) -> String {
+    let mut out = String::new();
+    self.write(&mut out, Some(indent), 0);
+    return out;
+  }
+}
+
+impl fmt::Display for JSONData {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut out = String::new();
+    self.write(&mut out, None, 0);
+    write!(f, "{}", out)
+  }
 }
 
-fn sum_numbers(json_data: &JSONData) -> i32 {
+fn sum_numbers(json_data: &JSONData) -> f64 {
   match json_data {
-    JSONData::JSONString(_) => return 0,
+    JSONData::JSONNull | JSONData::JSONBool(_) | JSONData::JSONString(_) => return 0.0,
 
     JSONData::JSONNumber(val) => return *val,
 
-    JSONData::JSONArray(vec) => 
+    JSONData::JSONArray(vec) =>
       return vec.iter().map(|data| sum_numbers(data)).sum(),
 
     JSONData::JSONObject(map) =>
@@ -84,20 +342,20 @@ fn sum_numbers(json_data: &JSONData) -> i32 {
   }
 }
 
-fn sum_numbers_ignore_red(json_data: &JSONData) -> i32 {
+fn sum_numbers_ignore_red(json_data: &JSONData) -> f64 {
   match json_data {
-    JSONData::JSONString(_) => return 0,
+    JSONData::JSONNull | JSONData::JSONBool(_) | JSONData::JSONString(_) => return 0.0,
 
     JSONData::JSONNumber(val) => return *val,
 
-    JSONData::JSONArray(vec) => 
+    JSONData::JSONArray(vec) =>
       return vec.iter().map(|data| sum_numbers_ignore_red(data)).sum(),
 
     JSONData::JSONObject(map) => {
-      let mut sum = 0;
+      let mut sum = 0.0;
       for (_, v) in map.iter() {
         if *v == JSONData::JSONString("red".to_string()) {
-          return 0;
+          return 0.0;
         } else {
           sum += sum_numbers_ignore_red(v);
         }
@@ -107,12 +365,457 @@ fn sum_numbers_ignore_red(json_data: &JSONData) -> i32 {
   }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+  Member(String),
+  Index(i64),
+  Wildcard,
+  Filter(Predicate)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Child(Selector),
+  Descendant(Selector),
+  Prune(Predicate)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+  Exists(Vec<Segment>),
+  Compare(Vec<Segment>, CompareOp, JSONData),
+  IsType(Vec<Segment>, String),
+  Not(Box<Predicate>),
+  And(Box<Predicate>, Box<Predicate>),
+  Or(Box<Predicate>, Box<Predicate>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregate {
+  Sum,
+  Count,
+  Min,
+  Max
+}
+
+const TYPE_NAMES: &[&str; 6] = &["object", "array", "string", "number", "boolean", "null"];
+
+/*
+  Query grammar, loosely after JSONPath:
+    query     := '$'? segment*
+    segment   := '.' name | '.*' | '..' (name | '*' | bracket) | bracket | '~(' predicate ')'
+    bracket   := '[' ('*' | integer | string | '?(' predicate ')') ']'
+    predicate := '!' predicate | '(' predicate ')' | predicate ('&&' | '||') predicate
+               | '@' segment* (op literal | 'is' type)?
+  Comparisons are existential over the nodes `@...` selects. A filter keeps
+  the children that pass, as in JSONPath, so `..[?()]` tests every node below
+  the current one. `~()` is not JSONPath: it gives each current node and
+  everything under it, except that a node failing the predicate is dropped
+  along with everything below it. That is what "ignore any object with a red
+  value" needs: `$~(!(@ is object && @.* == "red"))` sums to part 2.
+*/
+struct QueryParser<'a> {
+  bytes: &'a [u8],
+  index: usize
+}
+
+impl<'a> QueryParser<'a> {
+  fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+    return Err(ParseError{offset: self.index, message: message.to_string()});
+  }
+
+  fn peek(&self) -> Option<u8> {
+    return self.bytes.get(self.index).copied();
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t') = self.peek() { self.index += 1; }
+  }
+
+  fn eat(&mut self, token: &str) -> bool {
+    self.skip_whitespace();
+    if !self.bytes[self.index..].starts_with(token.as_bytes()) { return false; }
+    self.index += token.len();
+    return true;
+  }
+
+  fn expect(&mut self, token: &str) -> Result<(), ParseError> {
+    if !self.eat(token) { return self.error(&format!("expected '{}'", token)); }
+    return Ok(());
+  }
+
+  fn parse_query(&mut self) -> Result<Vec<Segment>, ParseError> {
+    self.eat("$");
+    let segments = self.parse_segments()?;
+    self.skip_whitespace();
+    if self.index != self.bytes.len() { return self.error("unexpected character in query"); }
+    return Ok(segments);
+  }
+
+  fn parse_segments(&mut self) -> Result<Vec<Segment>, ParseError> {
+    let mut segments = Vec::new();
+    loop {
+      if self.eat("..") {
+        segments.push(Segment::Descendant(self.parse_selector()?));
+      } else if self.eat(".") {
+        if self.peek() == Some(b'[') { return self.error("expected a member name"); }
+        segments.push(Segment::Child(self.parse_selector()?));
+      } else if self.peek() == Some(b'[') {
+        segments.push(Segment::Child(self.parse_selector()?));
+      } else if self.eat("~") {
+        self.expect("(")?;
+        segments.push(Segment::Prune(self.parse_or()?));
+        self.expect(")")?;
+      } else {
+        return Ok(segments);
+      }
+    }
+  }
+
+  fn parse_name(&mut self) -> String {
+    let start = self.index;
+    while let Some(b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'-') = self.peek() { self.index += 1; }
+    return String::from_utf8(self.bytes[start..self.index].to_vec()).unwrap();
+  }
+
+  fn parse_selector(&mut self) -> Result<Selector, ParseError> {
+    if self.eat("*") { return Ok(Selector::Wildcard); }
+    if !self.eat("[") {
+      let name = self.parse_name();
+      if name.is_empty() { return self.error("expected a member name, '*' or '['"); }
+      return Ok(Selector::Member(name));
+    }
+
+    self.skip_whitespace();
+    let selector = match self.peek() {
+      Some(b'*') => {
+        self.index += 1;
+        Selector::Wildcard
+      },
+      Some(b'?') => {
+        self.index += 1;
+        self.expect("(")?;
+        let predicate = self.parse_or()?;
+        self.expect(")")?;
+        Selector::Filter(predicate)
+      },
+      Some(b'\'' | b'"') => Selector::Member(self.parse_string_literal()?),
+      Some(b'-' | b'0'..=b'9') => {
+        let start = self.index;
+        self.index += 1;
+        while let Some(b'0'..=b'9') = self.peek() { self.index += 1; }
+        match std::str::from_utf8(&self.bytes[start..self.index]).unwrap().parse::<i64>() {
+          Ok(index) => Selector::Index(index),
+          Err(_) => {
+            self.index = start;
+            return self.error("invalid array index");
+          }
+        }
+      },
+      _ => return self.error("expected '*', an index, a string or a filter")
+    };
+    self.expect("]")?;
+    return Ok(selector);
+  }
+
+  fn parse_string_literal(&mut self) -> Result<String, ParseError> {
+    if self.peek() == Some(b'\'') {
+      let start = self.index + 1;
+      let end = match self.bytes[start..].iter().position(|b| *b == b'\'') {
+        Some(end) => start + end,
+        None => return self.error("unterminated string")
+      };
+      self.index = end + 1;
+      return Ok(String::from_utf8(self.bytes[start..end].to_vec()).unwrap());
+    }
+
+    let mut parser = Parser{bytes: self.bytes, index: self.index, depth: 0};
+    let string = parser.parse_string()?;
+    self.index = parser.index;
+    return Ok(string);
+  }
+
+  fn parse_literal(&mut self) -> Result<JSONData, ParseError> {
+    self.skip_whitespace();
+    if self.peek() == Some(b'\'') { return Ok(JSONData::JSONString(self.parse_string_literal()?)); }
+    let mut parser = Parser{bytes: self.bytes, index: self.index, depth: 0};
+    let value = parser.parse_value()?;
+    self.index = parser.index;
+    return Ok(value);
+  }
+
+  fn parse_or(&mut self) -> Result<Predicate, ParseError> {
+    let mut predicate = self.parse_and()?;
+    while self.eat("||") {
+      predicate = Predicate::Or(Box::new(predicate), Box::new(self.parse_and()?));
+    }
+    return Ok(predicate);
+  }
+
+  fn parse_and(&mut self) -> Result<Predicate, ParseError> {
+    let mut predicate = self.parse_unary()?;
+    while self.eat("&&") {
+      predicate = Predicate::And(Box::new(predicate), Box::new(self.parse_unary()?));
+    }
+    return Ok(predicate);
+  }
+
+  fn parse_unary(&mut self) -> Result<Predicate, ParseError> {
+    if self.eat("!") { return Ok(Predicate::Not(Box::new(self.parse_unary()?))); }
+    if self.eat("(") {
+      let predicate = self.parse_or()?;
+      self.expect(")")?;
+      return Ok(predicate);
+    }
+    self.expect("@")?;
+    let path = self.parse_segments()?;
+
+    if self.eat("is") {
+      self.skip_whitespace();
+      let type_name = self.parse_name();
+      if !TYPE_NAMES.contains(&type_name.as_str()) { return self.error("unknown type name"); }
+      return Ok(Predicate::IsType(path, type_name));
+    }
+
+    let ops = [("==", CompareOp::Eq), ("!=", CompareOp::Ne), ("<=", CompareOp::Le),
+      (">=", CompareOp::Ge), ("<", CompareOp::Lt), (">", CompareOp::Gt)];
+    for (token, op) in ops.iter() {
+      if self.eat(token) { return Ok(Predicate::Compare(path, *op, self.parse_literal()?)); }
+    }
+    return Ok(Predicate::Exists(path));
+  }
+}
+
+fn parse_query(query: &str) -> Result<Vec<Segment>, ParseError> {
+  return QueryParser{bytes: query.as_bytes(), index: 0}.parse_query();
+}
+
+fn children(node: &JSONData) -> Vec<&JSONData> {
+  return match node {
+    JSONData::JSONArray(values) => values.iter().collect(),
+    JSONData::JSONObject(key_values) => key_values.iter().map(|(_, value)| value).collect(),
+    _ => Vec::new()
+  };
+}
+
+fn type_name(node: &JSONData) -> &'static str {
+  return match node {
+    JSONData::JSONObject(_) => "object",
+    JSONData::JSONArray(_) => "array",
+    JSONData::JSONString(_) => "string",
+    JSONData::JSONNumber(_) => "number",
+    JSONData::JSONBool(_) => "boolean",
+    JSONData::JSONNull => "null"
+  };
+}
+
+fn compare(node: &JSONData, op: CompareOp, literal: &JSONData) -> bool {
+  let ordering = match (node, literal) {
+    (JSONData::JSONNumber(left), JSONData::JSONNumber(right)) => left.partial_cmp(right),
+    (JSONData::JSONString(left), JSONData::JSONString(right)) => Some(left.cmp(right)),
+    _ => None
+  };
+  return match op {
+    CompareOp::Eq => node == literal,
+    CompareOp::Ne => node != literal,
+    CompareOp::Lt => ordering.is_some_and(|o| o.is_lt()),
+    CompareOp::Le => ordering.is_some_and(|o| o.is_le()),
+    CompareOp::Gt => ordering.is_some_and(|o| o.is_gt()),
+    CompareOp::Ge => ordering.is_some_and(|o| o.is_ge())
+  };
+}
+
+impl Predicate {
+  fn matches(&self, node: &JSONData) -> bool {
+    return match self {
+      Predicate::Exists(path) => !evaluate_query(path, node).is_empty(),
+      Predicate::Compare(path, op, literal) =>
+        evaluate_query(path, node).iter().any(|found| compare(found, *op, literal)),
+      Predicate::IsType(path, name) =>
+        evaluate_query(path, node).iter().any(|found| type_name(found) == name),
+      Predicate::Not(predicate) => !predicate.matches(node),
+      Predicate::And(left, right) => left.matches(node) && right.matches(node),
+      Predicate::Or(left, right) => left.matches(node) || right.matches(node)
+    };
+  }
+}
+
+fn select<'a>(node: &'a JSONData, selector: &Selector, out: &mut Vec<&'a JSONData>) {
+  match (selector, node) {
+    (Selector::Member(name), JSONData::JSONObject(key_values)) =>
+      out.extend(key_values.iter().filter(|(key, _)| key == name).map(|(_, value)| value)),
+    (Selector::Index(index), JSONData::JSONArray(values)) => {
+      let index = if *index < 0 { values.len() as i64 + index } else { *index };
+      if index >= 0 && (index as usize) < values.len() { out.push(&values[index as usize]); }
+    },
+    (Selector::Wildcard, _) => out.extend(children(node)),
+    (Selector::Filter(predicate), _) =>
+      out.extend(children(node).into_iter().filter(|child| predicate.matches(child))),
+    _ => ()
+  }
+}
+
+fn descend<'a>(node: &'a JSONData, selector: &Selector, out: &mut Vec<&'a JSONData>) {
+  select(node, selector, out);
+  for child in children(node) {
+    descend(child, selector, out);
+  }
+}
+
+fn prune<'a>(node: &'a JSONData, predicate: &Predicate, out: &mut Vec<&'a JSONData>) {
+  if !predicate.matches(node) { return; }
+  out.push(node);
+  for child in children(node) {
+    prune(child, predicate, out);
+  }
+}
+
+fn evaluate_query<'a>(segments: &[Segment], root: &'a JSONData) -> Vec<&'a JSONData> {
+  let mut nodes = vec![root];
+  for segment in segments.iter() {
+    let mut next_nodes = Vec::new();
+    for node in nodes.iter() {
+      match segment {
+        Segment::Child(selector) => select(node, selector, &mut next_nodes),
+        Segment::Descendant(selector) => descend(node, selector, &mut next_nodes),
+        Segment::Prune(predicate) => prune(node, predicate, &mut next_nodes)
+      }
+    }
+    nodes = next_nodes;
+  }
+  return nodes;
+}
+
+fn aggregate_numbers(nodes: &[&JSONData], aggregate: Aggregate) -> Option<f64> {
+  let numbers = nodes.iter().filter_map(|node| match node {
+    JSONData::JSONNumber(val) => Some(*val),
+    _ => None
+  });
+  return match aggregate {
+    Aggregate::Sum => Some(numbers.sum()),
+    Aggregate::Count => Some(numbers.count() as f64),
+    Aggregate::Min => numbers.reduce(f64::min),
+    Aggregate::Max => numbers.reduce(f64::max)
+  };
+}
+
 fn main() {
+  let args = Args::parse();
   let document = fs::read_to_string("./data/q12.txt")
     .expect("Should have been able to read file");
 
-  let (_, json_data) = parse_json(&document.chars().collect::<Vec<char>>(), 0);
-  
+  let json_data = parse_json(&document)
+    .unwrap_or_else(|e| panic!("Should have been able to parse JSON: {}", e));
+
+  if args.flag("--pretty") { println!("{}", json_data.to_pretty(2)); }
+  if args.flag("--compact") { println!("{}", json_data); }
+
   println!("Part 1: {}", sum_numbers(&json_data));
   println!("Part 2: {}", sum_numbers_ignore_red(&json_data));
-}
\ No newline at end of file
+
+  if let Some(query) = args.value("--query") {
+    let segments = parse_query(query).unwrap_or_else(|e| panic!("Should have been able to parse query: {}", e));
+    let aggregate = match args.value("--aggregate").unwrap_or("sum") {
+      "sum" => Aggregate::Sum,
+      "count" => Aggregate::Count,
+      "min" => Aggregate::Min,
+      "max" => Aggregate::Max,
+      other => panic!("Unknown aggregate: {}", other)
+    };
+    match aggregate_numbers(&evaluate_query(&segments, &json_data), aggregate) {
+      Some(result) => println!("Query: {}", result),
+      None => println!("Query: no matching numbers")
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DOCUMENTS: &[&str] = &[
+    "null",
+    "true",
+    "[false, null, 0, -0.5, 1e3, 2.5E-3, 12345678901]",
+    r#"{"a": [1, 2, {"b": "red"}], "c": {}, "d": []}"#,
+    r#""escapes \" \\ \/ \b \f \n \r \t é 😀""#,
+    "  \n\t[ [ [ ] ] ]  \r\n"
+  ];
+
+  #[test]
+  fn round_trips_compact_and_pretty() {
+    for document in DOCUMENTS.iter() {
+      let value = parse_json(document).unwrap();
+      assert_eq!(parse_json(&value.to_string()).unwrap(), value, "compact: {}", document);
+      assert_eq!(parse_json(&value.to_pretty(2)).unwrap(), value, "pretty: {}", document);
+      assert_eq!(parse_json(&value.to_string()).unwrap().to_string(), value.to_string());
+    }
+  }
+
+  #[test]
+  fn decodes_escapes() {
+    assert_eq!(parse_json(r#""é😀\n""#).unwrap(), JSONData::JSONString("é😀\n".to_string()));
+    assert_eq!(parse_json(r#""\u00e9\u20AC""#).unwrap(), JSONData::JSONString("é€".to_string()));
+    assert_eq!(parse_json(r#""\ud83d\ude00""#).unwrap(), JSONData::JSONString("😀".to_string()));
+    assert_eq!(parse_json(r#""\ude00""#).unwrap_err().message, "unpaired surrogate");
+    assert_eq!(parse_json(r#""\u+0e9""#).unwrap_err().message, "invalid \\u escape");
+  }
+
+  #[test]
+  fn reports_error_offsets() {
+    let cases: &[(&str, usize)] = &[
+      ("", 0),
+      ("[1, 2", 5),
+      ("[1, 2,]", 6),
+      (r#"{"a" 1}"#, 5),
+      ("01", 1),
+      ("1.", 2),
+      ("tru", 0),
+      (r#""\x""#, 2),
+      ("\"abc", 4),
+      ("[1] x", 4),
+      (r#""\ud83d""#, 7)
+    ];
+    for (document, offset) in cases.iter() {
+      assert_eq!(parse_json(document).unwrap_err().offset, *offset, "{:?}", document);
+    }
+  }
+
+  #[test]
+  fn queries_reproduce_both_parts() {
+    let value = parse_json(r#"[1, {"c": "red", "b": 2}, {"d": [3, "red"]}, {"e": {"f": "red", "g": 4}}]"#).unwrap();
+    let all = parse_query("$..*").unwrap();
+    let not_red = parse_query(r#"$~(!(@ is object && @.* == "red"))"#).unwrap();
+    assert_eq!(aggregate_numbers(&evaluate_query(&all, &value), Aggregate::Sum), Some(sum_numbers(&value)));
+    assert_eq!(aggregate_numbers(&evaluate_query(&not_red, &value), Aggregate::Sum),
+      Some(sum_numbers_ignore_red(&value)));
+    assert_eq!(aggregate_numbers(&evaluate_query(&all, &value), Aggregate::Max), Some(4.0));
+    assert_eq!(aggregate_numbers(&evaluate_query(&parse_query("$[2].d[0]").unwrap(), &value), Aggregate::Count),
+      Some(1.0));
+    assert_eq!(parse_query("$..[?(@ >)]").unwrap_err().offset, 9);
+
+    let red_root = parse_json(r#"{"a": "red", "b": [1, {"c": 2}]}"#).unwrap();
+    assert_eq!(aggregate_numbers(&evaluate_query(&not_red, &red_root), Aggregate::Sum), Some(0.0));
+    let red_objects = parse_query(r#"$..[?(@ is object && @.* == "red")]"#).unwrap();
+    assert_eq!(evaluate_query(&red_objects, &value).len(), 2);
+    let below_red = parse_query(r#"$..[?(@ != "red")]"#).unwrap();
+    assert_eq!(aggregate_numbers(&evaluate_query(&below_red, &value), Aggregate::Sum), Some(sum_numbers(&value)));
+  }
+
+  #[test]
+  fn sums_real_world_documents() {
+    let value = parse_json(r#"{"a": [1, 2.5, true, null], "b": {"c": "red", "d": 4}}"#).unwrap();
+    assert_eq!(sum_numbers(&value), 7.5);
+    assert_eq!(sum_numbers_ignore_red(&value), 3.5);
+  }
+}
@@ -1,87 +1,316 @@
 use std::fs;
-use std::collections::HashMap;
-use std::collections::HashSet;
 use regex::Regex;
-use itertools::Itertools;
-
-fn calculate_max_happiness(
-  attendees: &HashSet<String>, 
-  potential_happiness: &HashMap<String, HashMap<String, i32>>
-) -> i32 
-{
-  let mut max_happiness: i32 = i32::MIN;
-
-  for perm in attendees.iter().permutations(attendees.len()) {
-    let mut curr_happiness: i32 = 0;
-    for i in 0..perm.len() {
-      let person = perm[i];
-      let left = perm[if i == 0 { perm.len() - 1 } else { i - 1 }];
-      let right = perm[(i + 1) % perm.len()];
-      curr_happiness += potential_happiness.get(person).unwrap().get(left).unwrap();
-      curr_happiness += potential_happiness.get(person).unwrap().get(right).unwrap();
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
+
+struct Table {
+  names: Vec<String>,
+  happiness: Vec<Vec<i32>>
+}
+
+impl Table {
+  fn new() -> Self {
+    return Table{names: Vec::new(), happiness: Vec::new()};
+  }
+
+  fn index_of(&mut self, name: &str) -> usize {
+    if let Some(index) = self.names.iter().position(|n| n == name) { return index; }
+    self.names.push(name.to_string());
+    for row in self.happiness.iter_mut() { row.push(0); }
+    self.happiness.push(vec![0; self.names.len()]);
+    return self.names.len() - 1;
+  }
+
+  fn find(&self, name: &str) -> Option<usize> {
+    return self.names.iter().position(|n| n == name);
+  }
+
+  fn set(&mut self, guest: &str, neighbour: &str, change: i32) {
+    let guest = self.index_of(guest);
+    let neighbour = self.index_of(neighbour);
+    self.happiness[guest][neighbour] = change;
+  }
+
+  fn with_neutral_guests(&self, names: &[String]) -> Table {
+    let mut table = Table{names: self.names.clone(), happiness: self.happiness.clone()};
+    for name in names.iter() { table.index_of(name); }
+    return table;
+  }
+
+  fn pair(&self, a: usize, b: usize) -> i32 {
+    return self.happiness[a][b] + self.happiness[b][a];
+  }
+}
+
+struct Constraints {
+  together: Vec<(String, String)>,
+  apart: Vec<(String, String)>,
+  seats: Vec<(String, usize)>
+}
+
+/* Constraints resolved against a particular table, as per-guest bitmasks. */
+struct Rules {
+  together: Vec<usize>,
+  apart: Vec<usize>,
+  seat: Vec<Option<usize>>
+}
+
+impl Rules {
+  /* Constraints naming a guest who isn't at this table don't apply to it. */
+  fn resolve(table: &Table, constraints: &Constraints) -> Option<Rules> {
+    let n = table.names.len();
+    let mut rules = Rules{together: vec![0; n], apart: vec![0; n], seat: vec![None; n]};
+    for (a, b) in constraints.together.iter() {
+      let (a, b) = match (table.find(a), table.find(b)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => continue
+      };
+      rules.together[a] |= 1 << b;
+      rules.together[b] |= 1 << a;
+    }
+    for (a, b) in constraints.apart.iter() {
+      let (a, b) = match (table.find(a), table.find(b)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => continue
+      };
+      rules.apart[a] |= 1 << b;
+      rules.apart[b] |= 1 << a;
+    }
+    for (name, seat) in constraints.seats.iter() {
+      let guest = match table.find(name) {
+        Some(guest) => guest,
+        None => continue
+      };
+      if *seat >= n { return None; }
+      if rules.seat[guest].is_some_and(|s| s != *seat) { return None; }
+      if rules.seat.iter().enumerate().any(|(other, s)| other != guest && *s == Some(*seat)) { return None; }
+      rules.seat[guest] = Some(*seat);
     }
+    for guest in 0..n {
+      if rules.together[guest].count_ones() > 2 { return None; }
+      if rules.together[guest] & rules.apart[guest] != 0 { return None; }
+    }
+    return Some(rules);
+  }
 
-    if curr_happiness > max_happiness { max_happiness = curr_happiness }
+  fn allowed_at(&self, guest: usize, seat: usize) -> bool {
+    return self.seat[guest].is_none_or(|s| s == seat);
   }
 
-  return max_happiness;
+  /* Whether `guest` may sit between `left` and `right`. */
+  fn neighbours_ok(&self, guest: usize, left: usize, right: usize) -> bool {
+    let neighbours = (1 << left) | (1 << right);
+    return self.together[guest] & !neighbours == 0 && self.apart[guest] & neighbours == 0;
+  }
 }
 
+#[derive(Clone, Copy)]
+struct Entry {
+  score: i32,
+  parent: usize,
+  parent_entry: usize
+}
 
-fn calculate_max_happiness_with_me(
-  attendees: &HashSet<String>, 
-  potential_happiness: &HashMap<String, HashMap<String, i32>>
-) -> i32 
-{
-  let mut attendees_with_me = attendees.clone();
-  let mut potential_happiness_with_me = potential_happiness.clone();
+struct Arrangement {
+  total: i32,
+  seats: Vec<usize>
+}
+
+fn push_bounded(entries: &mut Vec<Entry>, entry: Entry, k: usize) {
+  let position = entries.iter().position(|e| e.score < entry.score).unwrap_or(entries.len());
+  if position >= k { return; }
+  entries.insert(position, entry);
+  entries.truncate(k);
+}
+
+/*
+  Seats are filled in order from seat 0. best[state] keeps the k best partial
+  scores for a state of (guests seated, last guest seated, whether that guest's
+  left neighbour is one they must sit with); the last flag is all a guest's
+  left neighbour contributes to whether the next guest is allowed beside them,
+  so keeping k entries per state is enough to recover the k best tables. The
+  guests at seats 0 and 1 are fixed per pass so that the closing edge can check
+  the first guest's neighbours.
+*/
+fn plan_from(table: &Table, rules: &Rules, first: usize, second: usize, k: usize, found: &mut Vec<Arrangement>) {
+  let n = table.names.len();
+  let state = |mask: usize, last: usize, flag: bool| (mask * n + last) * 2 + flag as usize;
+  let mut best: Vec<Vec<Entry>> = vec![Vec::new(); (1 << n) * n * 2];
 
-  for (_, happiness) in potential_happiness_with_me.iter_mut() {
-    happiness.insert("Me".to_string(), 0);
+  let flag = rules.together[second] & (1 << first) != 0;
+  best[state((1 << first) | (1 << second), second, flag)]
+    .push(Entry{score: table.pair(first, second), parent: usize::MAX, parent_entry: 0});
+
+  let full = (1_usize << n) - 1;
+  for mask in 0..=full {
+    let seat = mask.count_ones() as usize;
+    if seat == n || mask & (1 << first) == 0 || mask & (1 << second) == 0 { continue; }
+    for last in 0..n {
+      for flag in [false, true] {
+        let from = state(mask, last, flag);
+        if best[from].is_empty() { continue; }
+        for next in 0..n {
+          if mask & (1 << next) != 0 || !rules.allowed_at(next, seat) { continue; }
+          if rules.apart[last] & (1 << next) != 0 { continue; }
+          let remaining = rules.together[last] & !(1 << next);
+          if remaining.count_ones() > flag as u32 { continue; }
+          let next_flag = rules.together[next] & (1 << last) != 0;
+          let to = state(mask | (1 << next), next, next_flag);
+          let edge = table.pair(last, next);
+          for index in 0..best[from].len() {
+            let score = best[from][index].score + edge;
+            push_bounded(&mut best[to], Entry{score, parent: from, parent_entry: index}, k);
+          }
+        }
+      }
+    }
   }
 
-  let me_values = attendees_with_me
-    .iter()
-    .map(|attendee| (attendee.clone(), 0 as i32))
-    .clone()
-    .collect::<HashMap<String, i32>>();
-  potential_happiness_with_me.insert("Me".to_string(), me_values);
-  attendees_with_me.insert("Me".to_string());
-  
-  return calculate_max_happiness(&attendees_with_me, &potential_happiness_with_me);
+  for last in 0..n {
+    for flag in [false, true] {
+      let from = state(full, last, flag);
+      for index in 0..best[from].len() {
+        let seats = reconstruct(&best, from, index, n);
+        let left = seats[seats.len().saturating_sub(2)];
+        if !rules.neighbours_ok(last, left, first) || !rules.neighbours_ok(first, last, second) { continue; }
+        found.push(Arrangement{total: best[from][index].score + table.pair(last, first), seats});
+      }
+    }
+  }
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q13.txt")
-    .expect("Should have been able to read file");
+fn reconstruct(best: &[Vec<Entry>], mut state: usize, mut index: usize, n: usize) -> Vec<usize> {
+  let mut seats = Vec::new();
+  loop {
+    let mask = state / 2 / n;
+    let last = state / 2 % n;
+    seats.push(last);
+    let entry = best[state][index];
+    if entry.parent == usize::MAX {
+      seats.push((mask ^ (1 << last)).trailing_zeros() as usize);
+      break;
+    }
+    state = entry.parent;
+    index = entry.parent_entry;
+  }
+  seats.reverse();
+  return seats;
+}
 
-  let mut potential_happiness: HashMap<String, HashMap<String, i32>> = HashMap::new();
-  let mut attendees: HashSet<String> = HashSet::new();
+fn plan(table: &Table, constraints: &Constraints, k: usize) -> Vec<Arrangement> {
+  let n = table.names.len();
+  let rules = match Rules::resolve(table, constraints) {
+    Some(rules) => rules,
+    None => return Vec::new()
+  };
+  if n < 2 {
+    return (0..n).map(|guest| Arrangement{total: 0, seats: vec![guest]}).take(k).collect();
+  }
+
+  /* With no guest pinned to a seat, rotations and reflections are the same table. */
+  let pinned = rules.seat.iter().any(|s| s.is_some());
+  let firsts: Vec<usize> = if pinned { (0..n).filter(|g| rules.allowed_at(*g, 0)).collect() } else { vec![0] };
+
+  let mut found = Vec::new();
+  for first in firsts.iter() {
+    for second in 0..n {
+      if second == *first || !rules.allowed_at(second, 1) { continue; }
+      plan_from(table, &rules, *first, second, k, &mut found);
+    }
+  }
+  if !pinned && n > 2 { found.retain(|arrangement| arrangement.seats[1] < arrangement.seats[n - 1]); }
+  found.sort_by(|a, b| b.total.cmp(&a.total).then(a.seats.cmp(&b.seats)));
+  found.truncate(k);
+  return found;
+}
+
+fn print_arrangements(table: &Table, arrangements: &[Arrangement]) {
+  let n = table.names.len();
+  for (rank, arrangement) in arrangements.iter().enumerate() {
+    println!("  #{} total {}", rank + 1, arrangement.total);
+    for (seat, guest) in arrangement.seats.iter().enumerate() {
+      let left = arrangement.seats[(seat + n - 1) % n];
+      let right = arrangement.seats[(seat + 1) % n];
+      let happiness = if n > 1 { table.happiness[*guest][left] + table.happiness[*guest][right] } else { 0 };
+      println!("    seat {:>2}: {:<10} {:>+5}", seat, table.names[*guest], happiness);
+    }
+  }
+}
+
+fn parse_pair(value: &str) -> (String, String) {
+  let (a, b) = value.split_once(',').unwrap_or_else(|| panic!("Expected A,B but got {}", value));
+  return (a.to_string(), b.to_string());
+}
+
+fn parse_table(contents: &str) -> Table {
+  let mut table = Table::new();
   let re = Regex::new("([A-Za-z]+) would (gain|lose) ([\\d]+) \
     happiness units by sitting next to ([A-Za-z]+).").unwrap();
 
-  for happiness in contents.split("\n") {
-    let cap = re.captures(happiness);
-    match cap {
+  for happiness in contents.lines().filter(|line| !line.is_empty()) {
+    match re.captures(happiness) {
       Some(matched_groups) => {
-        let key =  matched_groups[1].to_string();
-        if !potential_happiness.contains_key(&key) {
-          potential_happiness.insert(key.clone(), HashMap::new());
-        }
-
         let change_direction: i32 = if &matched_groups[2] == "gain" { 1 } else { -1 };
         let change: i32 = matched_groups[3].parse::<i32>().unwrap();
-        potential_happiness
-          .get_mut(&key)
-          .unwrap()
-          .insert(matched_groups[4].to_string(), change * change_direction);
-        attendees.insert(key);
+        table.set(&matched_groups[1], &matched_groups[4], change * change_direction);
       },
 
-      None => println!("Was unable to match line: {}", happiness),
+      None => panic!("Was unable to match line: {}", happiness)
+    }
+  }
+  return table;
+}
+
+/* The table without and with the extra neutral guests, for parts 1 and 2. */
+fn seatings(contents: &str, constraints: &Constraints, extra: usize) -> [Table; 2] {
+  let table = parse_table(contents);
+  let neutral: Vec<String> = (0..extra).map(|i| if i == 0 { "Me".to_string() } else { format!("Guest{}", i) }).collect();
+  let tables = [table.with_neutral_guests(&[]), table.with_neutral_guests(&neutral)];
+  let named = constraints.together.iter().chain(constraints.apart.iter()).flat_map(|(a, b)| [a, b])
+    .chain(constraints.seats.iter().map(|(name, _)| name));
+  for name in named {
+    if tables[1].find(name).is_none() { panic!("Unknown guest: {}", name); }
+  }
+  return tables;
+}
+
+fn solve(contents: &str, _ctx: &Context, constraints: &Constraints, extra: usize) -> Result<Vec<Answer>, Cancelled> {
+  return Ok(seatings(contents, constraints, extra).iter().map(|table| {
+    match plan(table, constraints, 1).first() {
+      Some(best) => best.total.into(),
+      None => "no seating satisfies the constraints".into()
+    }
+  }).collect());
+}
+
+fn main() {
+  let args = Args::parse();
+  let constraints = Constraints{
+    together: args.values("--together").iter().map(|v| parse_pair(v)).collect(),
+    apart: args.values("--apart").iter().map(|v| parse_pair(v)).collect(),
+    seats: args.values("--seat").iter().map(|v| {
+      let (name, seat) = v.split_once('=').unwrap_or_else(|| panic!("Expected Name=seat but got {}", v));
+      (name.to_string(), seat.parse::<usize>().unwrap())
+    }).collect()
+  };
+  let extra = args.value("--extra").map_or(1, |n| n.parse::<usize>().unwrap());
+
+  if let Some(top) = args.value("--top") {
+    let top = top.parse::<usize>().unwrap();
+    let contents = fs::read_to_string("./data/q13.txt")
+      .expect("Should have been able to read file");
+    for (part, table) in seatings(&contents, &constraints, extra).iter().enumerate() {
+      let arrangements = plan(table, &constraints, top);
+      match arrangements.first() {
+        Some(best) => println!("Part {}: {}", part + 1, best.total),
+        None => println!("Part {}: no seating satisfies the constraints", part + 1)
+      }
+      print_arrangements(table, &arrangements);
     }
+    return;
   }
 
-  println!("Part 1: {}", calculate_max_happiness(&attendees, &potential_happiness));
-  println!("Part 2: {}", calculate_max_happiness_with_me(&attendees, &potential_happiness));
-}
\ No newline at end of file
+  runner::run("q13", |contents, ctx| solve(contents, ctx, &constraints, extra));
+}
@@ -1,152 +1,283 @@
 use std::fs;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use regex::Regex;
-use std::collections::HashMap;
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 #[derive(Debug, Clone)]
 struct Reindeer {
   name: String,
   speed: u32,
   time_running: u32,
-  time_resting: u32,
-  rest_until: u32,
-  run_until: u32,
-  distance_ran: u32,
-  is_running: bool
+  time_resting: u32
 }
 
-impl Default for Reindeer {
-  fn default() -> Reindeer {
-    Reindeer { 
-      name: String::new(),
-      speed: 0, 
-      time_running: 0, 
-      time_resting: 0, 
-      rest_until: 0, 
-      run_until: 0, 
-      distance_ran: 0, 
-      is_running: false 
-    }
+impl Reindeer {
+  fn cycle(&self) -> u32 {
+    return self.time_running + self.time_resting;
+  }
+
+  fn distance_at(&self, second: u32) -> u32 {
+    let flown = second / self.cycle() * self.time_running + (second % self.cycle()).min(self.time_running);
+    return flown * self.speed;
+  }
+
+  fn is_running_after(&self, second: u32) -> bool {
+    return second % self.cycle() < self.time_running;
+  }
+
+  fn next_transition(&self, second: u32) -> u32 {
+    let into_cycle = second % self.cycle();
+    let start = second - into_cycle;
+    return if into_cycle < self.time_running { start + self.time_running } else { start + self.cycle() };
   }
 }
 
-fn calculate_longest_distance_travelled(
-  reindeers: &mut Vec<Reindeer>,
-  seconds: u32) -> u32 
-{
-  let mut curr_reindeers = reindeers.clone();
-
-  for second in 0..seconds {
-    for reindeer in curr_reindeers.iter_mut() {
-      if !reindeer.is_running && reindeer.rest_until == second {
-        reindeer.is_running = true;
-        reindeer.run_until = second + reindeer.time_running;
-      }
+/*
+  A scoring rule hands out points at the end of every second. It may only look
+  at the standings, which the simulator relies on: while no two reindeer swap
+  places the same award repeats, so it is applied once per stretch.
+*/
+trait Scoring {
+  fn award(&self, distances: &[u32]) -> Vec<u32>;
+}
 
-      if reindeer.is_running && reindeer.run_until == second {
-        reindeer.is_running = false;
-        reindeer.rest_until = second + reindeer.time_resting;
-      }
+struct LeadScoring;
 
-      if reindeer.is_running {
-        reindeer.distance_ran += reindeer.speed;
-      }
-    }
+impl Scoring for LeadScoring {
+  fn award(&self, distances: &[u32]) -> Vec<u32> {
+    let max_distance = *distances.iter().max().unwrap_or(&0);
+    return distances.iter().map(|d| if *d == max_distance { 1 } else { 0 }).collect();
   }
-  let mut max_distance: u32 = 0;
-  for reindeer in curr_reindeers.iter() {
-    if reindeer.distance_ran > max_distance { max_distance = reindeer.distance_ran }
+}
+
+struct PodiumScoring {
+  points: Vec<u32>
+}
+
+impl Scoring for PodiumScoring {
+  fn award(&self, distances: &[u32]) -> Vec<u32> {
+    return distances.iter().map(|d| {
+      let ahead = distances.iter().filter(|other| *other > d).count();
+      self.points.get(ahead).copied().unwrap_or(0)
+    }).collect();
   }
+}
 
-  return max_distance;
+fn scoring_from_name(name: &str) -> Box<dyn Scoring> {
+  return match name {
+    "lead" => Box::new(LeadScoring),
+    "podium" => Box::new(PodiumScoring{points: vec![3, 2, 1]}),
+    _ => panic!("Unknown scoring rule: {}", name)
+  };
 }
 
-fn get_leading_reindeers(reindeers: &&Vec<Reindeer>) -> Vec<String> {
-  let mut leading_reindeers: Vec<String> = Vec::new();
-  let mut max_distance: u32 = 0;
-  
-  for reindeer in reindeers.iter() {
-    if reindeer.distance_ran > max_distance { max_distance = reindeer.distance_ran }
+/* Seconds `first..=last` all award the same points. */
+struct Stretch {
+  first: u32,
+  last: u32,
+  award: Vec<u32>,
+  points_before: Vec<u32>
+}
+
+struct Timeline {
+  reindeers: Vec<Reindeer>,
+  seconds: u32,
+  stretches: Vec<Stretch>
+}
+
+impl Timeline {
+  fn distances_at(&self, second: u32) -> Vec<u32> {
+    return self.reindeers.iter().map(|reindeer| reindeer.distance_at(second)).collect();
+  }
+
+  /* Points are only known up to the second the race was simulated to. */
+  fn points_at(&self, second: u32) -> Vec<u32> {
+    if second > self.seconds { panic!("Second {} is past the end of the race at {}", second, self.seconds); }
+    let index = self.stretches.partition_point(|stretch| stretch.last < second);
+    return match self.stretches.get(index) {
+      Some(stretch) if stretch.first <= second => stretch.points_before.iter().zip(stretch.award.iter())
+        .map(|(before, award)| before + award * (second - stretch.first + 1))
+        .collect(),
+      _ => vec![0; self.reindeers.len()]
+    };
+  }
+
+  fn leaders(&self, values: &[u32]) -> Vec<String> {
+    let max_value = *values.iter().max().unwrap_or(&0);
+    return self.reindeers.iter().zip(values.iter())
+      .filter(|(_, value)| **value == max_value)
+      .map(|(reindeer, _)| reindeer.name.clone())
+      .collect();
+  }
+
+  fn to_csv(&self) -> String {
+    let mut csv = "second,reindeer,distance,points\n".to_string();
+    for second in 1..=self.seconds {
+      let distances = self.distances_at(second);
+      let points = self.points_at(second);
+      for (i, reindeer) in self.reindeers.iter().enumerate() {
+        csv.push_str(&format!("{},{},{},{}\n", second, reindeer.name, distances[i], points[i]));
+      }
+    }
+    return csv;
   }
 
-  for reindeer in reindeers.iter() {
-    if reindeer.distance_ran == max_distance {
-      leading_reindeers.push(reindeer.name.clone());
+  fn to_json(&self) -> String {
+    let mut rows = Vec::new();
+    for second in 1..=self.seconds {
+      let distances = self.distances_at(second);
+      let points = self.points_at(second);
+      let entries = self.reindeers.iter().enumerate()
+        .map(|(i, reindeer)| format!("{{\"reindeer\":\"{}\",\"distance\":{},\"points\":{}}}",
+          reindeer.name, distances[i], points[i]))
+        .collect::<Vec<String>>();
+      rows.push(format!("{{\"second\":{},\"standings\":[{}]}}", second, entries.join(",")));
     }
+    return format!("[\n{}\n]", rows.join(",\n"));
   }
-  
-  return leading_reindeers;
 }
 
+/*
+  Seconds in (from, to] where two reindeer may draw level or pass each other.
+  Every reindeer keeps a constant speed over the interval, so each pair can
+  cross at most once.
+*/
+fn crossing_seconds(reindeers: &[Reindeer], from: u32, to: u32) -> Vec<u32> {
+  let distances: Vec<i64> = reindeers.iter().map(|r| r.distance_at(from) as i64).collect();
+  let speeds: Vec<i64> = reindeers.iter()
+    .map(|r| if r.is_running_after(from) { r.speed as i64 } else { 0 })
+    .collect();
 
-fn calculate_highest_points(
-  reindeers: &mut Vec<Reindeer>,
-  seconds: u32) -> u32 
-{
-  let mut curr_reindeers = reindeers.clone();
-  let mut points = curr_reindeers
-    .iter()
-    .map(|reindeer| (reindeer.name.clone(), 0))
-    .collect::<HashMap<String, u32>>();
-
-  for second in 0..seconds {
-    for reindeer in curr_reindeers.iter_mut() {
-      if !reindeer.is_running && reindeer.rest_until == second {
-        reindeer.is_running = true;
-        reindeer.run_until = second + reindeer.time_running;
+  let mut seconds = vec![to];
+  for i in 0..reindeers.len() {
+    for j in (i + 1)..reindeers.len() {
+      if speeds[i] == speeds[j] { continue; }
+      let gap = distances[j] - distances[i];
+      let closing = speeds[i] - speeds[j];
+      if gap * closing < 0 { continue; }
+      let (floor, ceil) = (gap.abs() / closing.abs(), (gap.abs() + closing.abs() - 1) / closing.abs());
+      for offset in [floor, ceil] {
+        let second = from as i64 + offset;
+        if second > from as i64 && second < to as i64 { seconds.push(second as u32); }
       }
+    }
+  }
+  seconds.sort();
+  seconds.dedup();
+  return seconds;
+}
 
-      if reindeer.is_running && reindeer.run_until == second {
-        reindeer.is_running = false;
-        reindeer.rest_until = second + reindeer.time_resting;
-      }
+/*
+  Jumps from one fly/rest transition to the next. Between transitions the
+  standings only change where two reindeer cross, so each interval splits into
+  a handful of stretches instead of being stepped one second at a time.
+*/
+fn simulate(reindeers: &[Reindeer], seconds: u32, scoring: &dyn Scoring) -> Timeline {
+  let mut events: BinaryHeap<Reverse<(u32, usize)>> = reindeers.iter().enumerate()
+    .map(|(i, reindeer)| Reverse((reindeer.next_transition(0), i)))
+    .collect();
 
-      if reindeer.is_running {
-        reindeer.distance_ran += reindeer.speed;
+  let mut stretches: Vec<Stretch> = Vec::new();
+  let mut points = vec![0; reindeers.len()];
+  let mut now = 0;
+
+  while now < seconds {
+    let next = events.peek().map_or(seconds, |Reverse((time, _))| (*time).min(seconds));
+
+    let mut first = now + 1;
+    for boundary in crossing_seconds(reindeers, now, next) {
+      let mut spans = vec![(boundary, boundary)];
+      if first < boundary { spans.insert(0, (first, boundary - 1)); }
+      for (span_first, span_last) in spans {
+        let distances: Vec<u32> = reindeers.iter().map(|r| r.distance_at(span_first)).collect();
+        let award = scoring.award(&distances);
+        match stretches.last_mut() {
+          Some(stretch) if stretch.award == award => stretch.last = span_last,
+          _ => stretches.push(Stretch{first: span_first, last: span_last, award: award.clone(), points_before: points.clone()})
+        }
+        for (total, gained) in points.iter_mut().zip(award.iter()) { *total += gained * (span_last - span_first + 1); }
       }
+      first = boundary + 1;
     }
 
-    let reindeers_in_the_lead = get_leading_reindeers(&&curr_reindeers);
-    reindeers_in_the_lead.iter().for_each(|reindeer| 
-      *points.get_mut(reindeer).unwrap() += 1
-    );
+    now = next;
+    while let Some(Reverse((time, i))) = events.peek().copied() {
+      if time > now { break; }
+      events.pop();
+      events.push(Reverse((reindeers[i].next_transition(now), i)));
+    }
   }
 
-  return *points.values().max().unwrap();
+  return Timeline{reindeers: reindeers.to_vec(), seconds, stretches};
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q14.txt")
-    .expect("Should have been able to read file");
-
+fn parse_reindeers(contents: &str) -> Vec<Reindeer> {
   let re = Regex::new("([A-Za-z]+) can fly ([\\d]+) km/s \
     for ([\\d]+) seconds, but then must rest for ([\\d]+) seconds.").unwrap();
   let mut reindeers: Vec<Reindeer> = Vec::new();
 
-  for line in contents.split("\n") {
+  for line in contents.lines().filter(|line| !line.is_empty()) {
     let captures = re.captures(line);
     match captures {
       Some(matched_groups) => {
-        let reindeer_name = matched_groups[1].to_string();
-        let speed = matched_groups[2].parse::<u32>().unwrap();
-        let time_running = matched_groups[3].parse::<u32>().unwrap();
-        let time_resting = matched_groups[4].parse::<u32>().unwrap();
-
         let reindeer = Reindeer {
-          name: reindeer_name,
-          speed: speed, 
-          time_running: time_running, 
-          time_resting: time_resting,
-          ..Default::default()
+          name: matched_groups[1].to_string(),
+          speed: matched_groups[2].parse::<u32>().unwrap(),
+          time_running: matched_groups[3].parse::<u32>().unwrap(),
+          time_resting: matched_groups[4].parse::<u32>().unwrap()
         };
+        if reindeer.cycle() == 0 { panic!("{} neither flies nor rests: {}", reindeer.name, line); }
         reindeers.push(reindeer);
       },
 
-      None => {
-        println!("Was unable to match line: {}", line);
-      }
+      None => panic!("Was unable to match line: {}", line)
     }
   }
+  return reindeers;
+}
+
+fn solve(contents: &str, _ctx: &Context, seconds: u32, scoring: &str) -> Result<Vec<Answer>, Cancelled> {
+  let timeline = simulate(&parse_reindeers(contents), seconds, scoring_from_name(scoring).as_ref());
+  return Ok(vec![
+    (*timeline.distances_at(seconds).iter().max().unwrap_or(&0)).into(),
+    (*timeline.points_at(seconds).iter().max().unwrap_or(&0)).into()
+  ]);
+}
 
-  println!("Part 1: {}", calculate_longest_distance_travelled(&mut reindeers, 2503));
-  println!("Part 2: {}", calculate_highest_points(&mut reindeers, 2503));
-}
\ No newline at end of file
+fn main() {
+  let args = Args::parse();
+  let seconds = args.value("--seconds").map_or(2503, |s| s.parse::<u32>().unwrap());
+  let scoring = args.value("--scoring").unwrap_or("lead");
+  let leader_at = args.values("--leader-at").iter().map(|s| s.parse::<u32>().unwrap()).collect::<Vec<u32>>();
+
+  if let Some(format) = args.value("--export") {
+    let contents = fs::read_to_string("./data/q14.txt")
+      .expect("Should have been able to read file");
+    let timeline = simulate(&parse_reindeers(&contents), seconds, scoring_from_name(scoring).as_ref());
+    match format {
+      "csv" => print!("{}", timeline.to_csv()),
+      "json" => println!("{}", timeline.to_json()),
+      _ => panic!("Unknown export format: {}", format)
+    }
+    return;
+  }
+
+  if !leader_at.is_empty() {
+    let contents = fs::read_to_string("./data/q14.txt")
+      .expect("Should have been able to read file");
+    let horizon = leader_at.iter().copied().fold(seconds, u32::max);
+    let timeline = simulate(&parse_reindeers(&contents), horizon, scoring_from_name(scoring).as_ref());
+    for second in leader_at {
+      println!("At {}s: {} leads on distance, {} on points", second,
+        timeline.leaders(&timeline.distances_at(second)).join(", "),
+        timeline.leaders(&timeline.points_at(second)).join(", "));
+    }
+    return;
+  }
+
+  runner::run("q14", |contents, ctx| solve(contents, ctx, seconds, scoring));
+}
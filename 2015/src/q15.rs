@@ -1,146 +1,233 @@
 use std::fs;
-use regex::Regex;
+use std::collections::HashMap;
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 #[derive(Debug)]
 struct Ingredient {
-  capacity: i32,
-  durability: i32,
-  flavor: i32,
-  texture: i32,
-  calories: i32
+  name: String,
+  properties: Vec<i64>,
+  min_amount: i64,
+  max_amount: Option<i64>
 }
 
-impl Ingredient {
-  fn get_capacity_score(&self, amount: u32) -> i32 {
-    return self.capacity * amount as i32;
-  }
+struct Pantry {
+  property_names: Vec<String>,
+  ingredients: Vec<Ingredient>
+}
 
-  fn get_durability_score(&self, amount: u32) -> i32 {
-    return self.durability * amount as i32;
+impl Pantry {
+  fn property(&self, name: &str) -> usize {
+    return self.property_names.iter().position(|p| p == name)
+      .unwrap_or_else(|| panic!("Unknown property: {}", name));
   }
 
-  fn get_flavor_score(&self, amount: u32) -> i32 {
-    return self.flavor * amount as i32;
+  fn ingredient_mut(&mut self, name: &str) -> &mut Ingredient {
+    return self.ingredients.iter_mut().find(|i| i.name == name)
+      .unwrap_or_else(|| panic!("Unknown ingredient: {}", name));
   }
+}
 
-  fn get_texture_score(&self, amount: u32) -> i32 {
-    return self.texture * amount as i32;
+fn parse_pantry(contents: &str) -> Pantry {
+  let mut pantry = Pantry{property_names: Vec::new(), ingredients: Vec::new()};
+  let mut rows: Vec<(String, HashMap<String, i64>)> = Vec::new();
+
+  for line in contents.lines().filter(|line| !line.is_empty()) {
+    let (name, properties) = match line.split_once(": ") {
+      Some(split) => split,
+      None => panic!("Was unable to match line: {}", line)
+    };
+    let mut values = HashMap::new();
+    for property in properties.split(", ") {
+      let (property, value) = property.split_once(' ').unwrap();
+      if !pantry.property_names.iter().any(|p| p == property) { pantry.property_names.push(property.to_string()); }
+      values.insert(property.to_string(), value.parse::<i64>().unwrap());
+    }
+    rows.push((name.to_string(), values));
   }
 
-  fn get_calories(&self, amount: u32) -> i32 {
-    return self.calories * amount as i32;
+  for (name, values) in rows {
+    let properties = pantry.property_names.iter().map(|p| *values.get(p).unwrap_or(&0)).collect();
+    pantry.ingredients.push(Ingredient{name, properties, min_amount: 1, max_amount: None});
   }
+  return pantry;
 }
 
-fn get_combinations(total: u32, parts: u32) -> Vec<Vec<u32>> {
-  if parts == 1 { return vec![vec![total]] }
-
-  let mut combinations: Vec<Vec<u32>> = Vec::new();
+/* Keeps the total of one property within `min..=max`. */
+#[derive(Clone, Copy, Debug)]
+struct Constraint {
+  property: usize,
+  min: i64,
+  max: i64
+}
 
-  for i in 1..(total + 1) - (parts - 1) {
-    let curr_comb = vec![i];
-    let mut next_combs = get_combinations(total - i, parts - 1);
-    for next_comb in next_combs.iter_mut() {
-      let mut total_combination = curr_comb.clone();
-      total_combination.append(next_comb);
-      combinations.push(total_combination);
+fn parse_constraint(pantry: &Pantry, text: &str) -> Constraint {
+  let (property, min, max) = if let Some((property, value)) = text.split_once(">=") {
+    (property, value.parse::<i64>().unwrap(), i64::MAX)
+  } else if let Some((property, value)) = text.split_once("<=") {
+    (property, i64::MIN, value.parse::<i64>().unwrap())
+  } else if let Some((property, value)) = text.split_once('=') {
+    match value.split_once("..") {
+      Some((lo, hi)) => (property, lo.parse::<i64>().unwrap(), hi.parse::<i64>().unwrap()),
+      None => (property, value.parse::<i64>().unwrap(), value.parse::<i64>().unwrap())
     }
-  }
+  } else {
+    panic!("Expected prop=v, prop=lo..hi, prop>=v or prop<=v but got {}", text);
+  };
+  return Constraint{property: pantry.property(property), min, max};
+}
+
+struct Recipe {
+  score: u128,
+  amounts: Vec<i64>
+}
 
-  return combinations;
+struct Search<'a> {
+  pantry: &'a Pantry,
+  scored: Vec<usize>,
+  constraints: Vec<Constraint>,
+  amounts: Vec<i64>,
+  best: Option<Recipe>
 }
 
-fn get_highest_score(ingredients: &Vec<Ingredient>) -> u32 {
-  let combinations = get_combinations(100, ingredients.len() as u32);
-
-  let mut total_score: u32 = 0;
-  for combination in combinations.iter() {
-    let mut capacity_score: i32 = 0;
-    let mut durability_score: i32 = 0;
-    let mut flavor_score: i32 = 0;
-    let mut texture_score: i32 = 0;
-
-    for i in 0..combination.len() {
-      capacity_score += ingredients[i].get_capacity_score(combination[i]);
-      durability_score += ingredients[i].get_durability_score(combination[i]);
-      flavor_score += ingredients[i].get_flavor_score(combination[i]);
-      texture_score += ingredients[i].get_texture_score(combination[i]);
+impl<'a> Search<'a> {
+  /*
+    Bounds on the final total of `property` given the ingredients from `next`
+    on still share `remaining` teaspoons: every one gets its minimum, and the
+    rest goes wherever it helps (or hurts) the most. Per-ingredient maximums
+    are ignored, so the range can only be too wide, never too narrow.
+  */
+  fn range(&self, totals: &[i64], property: usize, next: usize, remaining: i64) -> (i64, i64) {
+    let rest = &self.pantry.ingredients[next..];
+    let spare = remaining - rest.iter().map(|i| i.min_amount).sum::<i64>();
+    let base = totals[property] + rest.iter().map(|i| i.min_amount * i.properties[property]).sum::<i64>();
+    let lowest = rest.iter().map(|i| i.properties[property]).min().unwrap_or(0);
+    let highest = rest.iter().map(|i| i.properties[property]).max().unwrap_or(0);
+    return (base + spare * lowest, base + spare * highest);
+  }
+
+  fn score(&self, totals: &[i64]) -> u128 {
+    return self.scored.iter().map(|p| totals[*p].max(0) as u128).product();
+  }
+
+  fn search(&mut self, totals: &mut Vec<i64>, next: usize, remaining: i64) {
+    let ingredients = &self.pantry.ingredients;
+    if next == ingredients.len() {
+      if remaining != 0 { return; }
+      if self.constraints.iter().any(|c| totals[c.property] < c.min || totals[c.property] > c.max) { return; }
+      let score = self.score(totals);
+      if self.best.as_ref().is_none_or(|best| score > best.score) {
+        self.best = Some(Recipe{score, amounts: self.amounts.clone()});
+      }
+      return;
     }
 
-    if capacity_score <= 0 || durability_score <= 0 || 
-      flavor_score <= 0 || texture_score <= 0 { continue }
-    
-    let curr_score: u32 = capacity_score as u32 * durability_score as u32 * 
-      flavor_score as u32 * texture_score as u32;
+    let spare = remaining - ingredients[next..].iter().map(|i| i.min_amount).sum::<i64>();
+    if spare < 0 { return; }
+    for c in self.constraints.iter() {
+      let (lo, hi) = self.range(totals, c.property, next, remaining);
+      if hi < c.min || lo > c.max { return; }
+    }
+    if let Some(best) = self.best.as_ref() {
+      let bound: u128 = self.scored.iter().map(|p| self.range(totals, *p, next, remaining).1.max(0) as u128).product();
+      if bound <= best.score { return; }
+    }
 
-    if total_score < curr_score { total_score = curr_score }
+    let ingredient = &ingredients[next];
+    let lowest = ingredient.min_amount;
+    let highest = ingredient.max_amount.unwrap_or(i64::MAX).min(lowest + spare);
+    let amounts = if next + 1 == ingredients.len() { remaining..=remaining } else { lowest..=highest };
+    for amount in amounts.rev() {
+      if amount < lowest || amount > highest { continue; }
+      for (total, value) in totals.iter_mut().zip(ingredient.properties.iter()) { *total += amount * value; }
+      self.amounts[next] = amount;
+      self.search(totals, next + 1, remaining - amount);
+      for (total, value) in totals.iter_mut().zip(ingredient.properties.iter()) { *total -= amount * value; }
+    }
   }
+}
 
-  return total_score;
+/*
+  Branch and bound over the amount of each ingredient in turn, pruning a branch
+  as soon as a constraint can no longer be met or the best score it could still
+  reach is no better than the best recipe found so far.
+*/
+fn optimise(pantry: &Pantry, teaspoons: i64, scored: &[usize], constraints: &[Constraint]) -> Option<Recipe> {
+  let mut search = Search{
+    pantry,
+    scored: scored.to_vec(),
+    constraints: constraints.to_vec(),
+    amounts: vec![0; pantry.ingredients.len()],
+    best: None
+  };
+  search.search(&mut vec![0; pantry.property_names.len()], 0, teaspoons);
+  return search.best;
 }
 
-fn get_highest_score_with_calorie_check(ingredients: &Vec<Ingredient>) -> u32 {
-  let combinations = get_combinations(100, ingredients.len() as u32);
-
-  let mut total_score: u32 = 0;
-  for combination in combinations.iter() {
-    let mut capacity_score: i32 = 0;
-    let mut durability_score: i32 = 0;
-    let mut flavor_score: i32 = 0;
-    let mut texture_score: i32 = 0;
-    let mut calories: i32 = 0;
-
-    for i in 0..combination.len() {
-      capacity_score += ingredients[i].get_capacity_score(combination[i]);
-      durability_score += ingredients[i].get_durability_score(combination[i]);
-      flavor_score += ingredients[i].get_flavor_score(combination[i]);
-      texture_score += ingredients[i].get_texture_score(combination[i]);
-      calories += ingredients[i].get_calories(combination[i]);
-    }
+/* The best recipes for parts 1 and 2 under the bounds and constraints given on the command line. */
+fn best_recipes(contents: &str, args: &Args) -> (Pantry, [Option<Recipe>; 2]) {
+  let mut pantry = parse_pantry(contents);
 
-    if capacity_score <= 0 || durability_score <= 0 || 
-      flavor_score <= 0 || texture_score <= 0 { continue }
+  for bound in args.values("--min") {
+    let (name, amount) = bound.split_once('=').unwrap();
+    pantry.ingredient_mut(name).min_amount = amount.parse::<i64>().unwrap();
+  }
+  for bound in args.values("--max") {
+    let (name, amount) = bound.split_once('=').unwrap();
+    pantry.ingredient_mut(name).max_amount = Some(amount.parse::<i64>().unwrap());
+  }
 
-    if calories != 500 { continue }
-    
-    let curr_score: u32 = capacity_score as u32 * durability_score as u32 * 
-      flavor_score as u32 * texture_score as u32;
+  let teaspoons = args.value("--teaspoons").map_or(100, |t| t.parse::<i64>().unwrap());
+  let scored: Vec<usize> = match args.value("--score") {
+    Some(names) => names.split(',').map(|name| pantry.property(name)).collect(),
+    None => (0..pantry.property_names.len()).filter(|p| pantry.property_names[*p] != "calories").collect()
+  };
+  let constraints: Vec<Constraint> = args.values("--require").iter()
+    .map(|text| parse_constraint(&pantry, text))
+    .collect();
+
+  let mut with_calories = constraints.clone();
+  with_calories.push(parse_constraint(&pantry, "calories=500"));
+  let recipes = [
+    optimise(&pantry, teaspoons, &scored, &constraints),
+    optimise(&pantry, teaspoons, &scored, &with_calories)
+  ];
+  return (pantry, recipes);
+}
 
-    if total_score < curr_score { total_score = curr_score }
+fn print_recipe(part: u32, pantry: &Pantry, recipe: Option<Recipe>) {
+  match recipe {
+    Some(recipe) => {
+      println!("Part {}: {}", part, recipe.score);
+      for (ingredient, amount) in pantry.ingredients.iter().zip(recipe.amounts.iter()) {
+        println!("  {}: {}", ingredient.name, amount);
+      }
+    },
+    None => println!("Part {}: no recipe meets the constraints", part)
   }
+}
 
-  return total_score;
+fn solve(contents: &str, _ctx: &Context, args: &Args) -> Result<Vec<Answer>, Cancelled> {
+  let (_, recipes) = best_recipes(contents, args);
+  return Ok(recipes.into_iter().map(|recipe| match recipe {
+    Some(recipe) => recipe.score.into(),
+    None => "no recipe meets the constraints".into()
+  }).collect());
 }
 
 fn main() {
-  let contents = fs::read_to_string("./data/q15.txt")
-    .expect("Should have been able to read file");
-
-  let re = Regex::new("[A-Za-z]+: \
-    capacity (-?[\\d]+), durability (-?[\\d]+), flavor (-?[\\d]+), \
-    texture (-?[\\d]+), calories (-?[\\d]+)").unwrap();
-  let mut ingredients: Vec<Ingredient> = Vec::new();
-
-  contents.split("\n").for_each(|line| {
-    let captures = re.captures(line);
-
-    match captures {
-      Some(matched_groups) => {
-        let ingredient = Ingredient {
-          capacity: matched_groups[1].parse::<i32>().unwrap(),
-          durability: matched_groups[2].parse::<i32>().unwrap(),
-          flavor: matched_groups[3].parse::<i32>().unwrap(),
-          texture: matched_groups[4].parse::<i32>().unwrap(),
-          calories: matched_groups[5].parse::<i32>().unwrap(),
-        };
-
-        ingredients.push(ingredient);
-      },
-
-      None => println!("Was unable to match line: {}", line)
-    }
-  });
+  let args = Args::parse();
+
+  if args.flag("--recipe") {
+    let contents = fs::read_to_string("./data/q15.txt")
+      .expect("Should have been able to read file");
+    let (pantry, [first, second]) = best_recipes(&contents, &args);
+    print_recipe(1, &pantry, first);
+    print_recipe(2, &pantry, second);
+    return;
+  }
 
-  println!("Part 1: {}", get_highest_score(&ingredients));
-  println!("Part 2: {}", get_highest_score_with_calorie_check(&ingredients));
-}
\ No newline at end of file
+  runner::run("q15", |contents, ctx| solve(contents, ctx, &args));
+}
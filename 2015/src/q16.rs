@@ -1,99 +1,182 @@
-use std::{fs, collections::HashMap};
-use regex::Regex;
+use std::{fs, fmt, collections::HashMap};
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
+
+const MFCSAM_READING: &str = "children=3 cats=7 samoyeds=2 pomeranians=3 akitas=0 \
+  vizslas=0 goldfish=5 trees=3 cars=2 perfumes=1";
+const RETROENCABULATOR_READING: &str = "children=3 cats>7 samoyeds=2 pomeranians<3 akitas=0 \
+  vizslas=0 goldfish<5 trees>3 cars=2 perfumes=1";
 
 #[derive(Debug)]
 struct Sue {
   id: u32,
-  attributes: Vec<(String, u32)>,
+  attributes: HashMap<String, u32>,
 }
 
-fn find_my_sue_id(sues: &Vec<Sue>, my_sue: &HashMap<String, u32>) -> u32 {
-  for sue in sues.iter() {
-    let mut found = true;
-    for (key, value) in sue.attributes.iter() {
-      let my_sue_value = my_sue.get(key).unwrap();
-      if my_sue_value != value {
-        found = false;
-        break;
-      }
+#[derive(Debug, PartialEq)]
+struct ParseError {
+  offset: usize,
+  message: String
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} at byte {}", self.message, self.offset)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge
+}
+
+impl CompareOp {
+  fn holds(&self, left: u32, right: u32) -> bool {
+    return match self {
+      CompareOp::Eq => left == right,
+      CompareOp::Ne => left != right,
+      CompareOp::Lt => left < right,
+      CompareOp::Le => left <= right,
+      CompareOp::Gt => left > right,
+      CompareOp::Ge => left >= right
+    };
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Predicate {
+  property: String,
+  op: CompareOp,
+  value: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+  Matched,
+  Contradicted,
+  Unknown
+}
+
+impl Predicate {
+  /* A Sue's record is incomplete, so a property she doesn't list could be anything. */
+  fn test(&self, sue: &Sue) -> Outcome {
+    return match sue.attributes.get(&self.property) {
+      Some(value) if self.op.holds(*value, self.value) => Outcome::Matched,
+      Some(_) => Outcome::Contradicted,
+      None => Outcome::Unknown
+    };
+  }
+}
+
+/* Whitespace- or comma-separated terms such as `cats>7` or `akitas=0`. */
+fn parse_query(query: &str) -> Result<Vec<Predicate>, ParseError> {
+  let bytes = query.as_bytes();
+  let mut predicates = Vec::new();
+  let mut index = 0;
+
+  loop {
+    while index < bytes.len() && (bytes[index].is_ascii_whitespace() || bytes[index] == b',') { index += 1; }
+    if index == bytes.len() { return Ok(predicates); }
+
+    let start = index;
+    while index < bytes.len() && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_') { index += 1; }
+    if start == index {
+      return Err(ParseError{offset: index, message: "expected a property name".to_string()});
     }
-    if found { return sue.id }
+    let property = query[start..index].to_string();
+
+    let op = match &bytes[index..(index + 2).min(bytes.len())] {
+      [b'=', b'=', ..] => (CompareOp::Eq, 2),
+      [b'!', b'=', ..] => (CompareOp::Ne, 2),
+      [b'<', b'=', ..] => (CompareOp::Le, 2),
+      [b'>', b'=', ..] => (CompareOp::Ge, 2),
+      [b'=', ..] => (CompareOp::Eq, 1),
+      [b'<', ..] => (CompareOp::Lt, 1),
+      [b'>', ..] => (CompareOp::Gt, 1),
+      _ => return Err(ParseError{offset: index, message: "expected one of = == != < <= > >=".to_string()})
+    };
+    index += op.1;
+
+    let start = index;
+    while index < bytes.len() && bytes[index].is_ascii_digit() { index += 1; }
+    let value = query[start..index].parse::<u32>()
+      .map_err(|_| ParseError{offset: start, message: "expected a count".to_string()})?;
+    predicates.push(Predicate{property, op: op.0, value});
   }
-  return 0;
 }
 
-fn find_my_sue_real_id(sues: &Vec<Sue>, my_sue: &HashMap<String, u32>) -> u32 {
+struct Candidate<'a> {
+  sue: &'a Sue,
+  matched: usize,
+  unknown: usize
+}
+
+/* Every Sue no predicate rules out, best supported first. */
+fn rank_sues<'a>(sues: &'a [Sue], predicates: &[Predicate]) -> Vec<Candidate<'a>> {
+  let mut candidates = Vec::new();
   for sue in sues.iter() {
-    let mut found = true;
-    for (key, value) in sue.attributes.iter() {
-      let my_sue_value = my_sue.get(key).unwrap();
-      match key.as_str() {
-        "cats" | "trees" => {
-          if value < my_sue_value {
-            found = false;
-            break;
-          }
-        },
-
-        "pomeranians" | "goldfish" => {
-          if value > my_sue_value {
-            found = false;
-            break;
-          }
-        },
-
-        _ => {
-          if value != my_sue_value {
-            found = false;
-            break;
-          }
-        }
-      }
-    }
-    if found { return sue.id }
+    let outcomes: Vec<Outcome> = predicates.iter().map(|predicate| predicate.test(sue)).collect();
+    if outcomes.contains(&Outcome::Contradicted) { continue; }
+    candidates.push(Candidate{
+      sue,
+      matched: outcomes.iter().filter(|o| **o == Outcome::Matched).count(),
+      unknown: outcomes.iter().filter(|o| **o == Outcome::Unknown).count()
+    });
   }
-  return 0;
+  candidates.sort_by(|a, b| b.matched.cmp(&a.matched).then(a.sue.id.cmp(&b.sue.id)));
+  return candidates;
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q16.txt")
-    .expect("Should have been able to read file");
-
-  let my_sue: HashMap<String, u32> = HashMap::from_iter([
-    ("children".to_string(), 3),
-    ("cats".to_string(), 7),
-    ("samoyeds".to_string(), 2),
-    ("pomeranians".to_string(), 3),
-    ("akitas".to_string(), 0),
-    ("vizslas".to_string(), 0),
-    ("goldfish".to_string(), 5),
-    ("trees".to_string(), 3),
-    ("cars".to_string(), 2),
-    ("perfumes".to_string(), 1),
+fn find_my_sue(sues: &[Sue], query: &str) -> u32 {
+  let predicates = parse_query(query).unwrap_or_else(|e| panic!("Should have been able to parse query: {}", e));
+  return rank_sues(sues, &predicates).first().map_or(0, |candidate| candidate.sue.id);
+}
+
+fn parse_sue(line: &str) -> Option<Sue> {
+  let (name, attributes) = line.split_once(": ")?;
+  let id = name.strip_prefix("Sue ")?.parse::<u32>().ok()?;
+  let mut sue = Sue{id, attributes: HashMap::new()};
+  for attribute in attributes.split(", ") {
+    let (key, value) = attribute.split_once(": ")?;
+    sue.attributes.insert(key.to_string(), value.parse::<u32>().ok()?);
+  }
+  return Some(sue);
+}
+
+fn parse_sues(contents: &str) -> Vec<Sue> {
+  return contents.lines().filter(|line| !line.is_empty())
+    .map(|line| parse_sue(line).unwrap_or_else(|| panic!("Was unable to match line: {}", line)))
+    .collect();
+}
+
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
+  let sues = parse_sues(contents);
+  return Ok(vec![
+    find_my_sue(&sues, MFCSAM_READING).into(),
+    find_my_sue(&sues, RETROENCABULATOR_READING).into()
   ]);
-  let mut sues: Vec<Sue> = Vec::new();
-  let re = Regex::new("Sue ([\\d]+): ([a-z]+): \
-    ([\\d]+), ([a-z]+): ([\\d]+), ([a-z]+): ([\\d]+)").unwrap();
-
-  contents.split("\n").for_each(|line| {
-    let captures = re.captures(line);
-    match captures {
-      Some(matched_groups) => {
-        let id: u32 = matched_groups[1].parse::<u32>().unwrap();
-        let mut attributes: Vec<(String, u32)> = Vec::new();
-
-        for i in (2..matched_groups.len()).step_by(2) {
-          let key = matched_groups[i].to_string();
-          let value = matched_groups[i + 1].parse::<u32>().unwrap();
-          attributes.push((key, value));
-        }
-
-        sues.push(Sue { id: id, attributes: attributes });
-      }
-      None => println!("Was unable to match line: {}", line),
+}
+
+fn main() {
+  let args = Args::parse();
+
+  if let Some(query) = args.value("--query") {
+    let contents = fs::read_to_string("./data/q16.txt")
+      .expect("Should have been able to read file");
+    let sues = parse_sues(&contents);
+    let predicates = parse_query(query).unwrap_or_else(|e| panic!("Should have been able to parse query: {}", e));
+    for candidate in rank_sues(&sues, &predicates) {
+      println!("Sue {}: {} matched, {} unknown", candidate.sue.id, candidate.matched, candidate.unknown);
     }
-  });
+    return;
+  }
 
-  println!("Part 1: {}", find_my_sue_id(&sues, &my_sue));
-  println!("Part 2: {}", find_my_sue_real_id(&sues, &my_sue));
-}
\ No newline at end of file
+  runner::run("q16", solve);
+}
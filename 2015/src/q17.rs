@@ -1,82 +1,145 @@
 use std::fs;
+use num_bigint::{BigInt, BigUint};
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
+
+/*
+  ways[count][size] is the number of ways to pick exactly `count` containers
+  holding `size` litres between them. Containers are folded in one at a time,
+  walking count and size downwards so that none is used twice.
+*/
+fn count_ways_by_containers(containers: &[usize], total_size: usize, ctx: &Context) -> Result<Vec<BigUint>, Cancelled> {
+  let mut ways: Vec<Vec<BigUint>> = vec![vec![BigUint::ZERO; total_size + 1]; containers.len() + 1];
+  ways[0][0] = BigUint::from(1_u32);
+
+  for (used, container) in containers.iter().enumerate() {
+    ctx.progress("containers folded", used as u64, Some(containers.len() as u64))?;
+    if *container > total_size { continue; }
+    for count in (1..=used + 1).rev() {
+      for size in (*container..=total_size).rev() {
+        if ways[count - 1][size - container] == BigUint::ZERO { continue; }
+        let extra = ways[count - 1][size - container].clone();
+        ways[count][size] += extra;
+      }
+    }
+  }
 
-fn count_combinations(containers: &Vec<u32>, curr_size: u32, total_size: u32) -> u32 {
-  if total_size == curr_size { return 1 }
-
-  let mut combinations: u32 = 0;
-  let mut next_containers: Vec<u32> = containers.clone();
-  for i in 0..containers.len() {
-    if curr_size + containers[i] > total_size { break }
+  return Ok(ways.into_iter().map(|row| row[total_size].clone()).collect());
+}
 
-    let next_size = curr_size + containers[i];
-    next_containers.remove(0);
-    combinations += count_combinations(&next_containers, next_size, total_size);
-  }
+fn count_combinations_that_fit(ways_by_containers: &[BigUint]) -> BigUint {
+  return ways_by_containers.iter().sum();
+}
 
-  return combinations;
+fn count_ways_to_fill_minimum_containers(ways_by_containers: &[BigUint]) -> BigUint {
+  return ways_by_containers.iter().find(|ways| **ways != BigUint::ZERO).cloned().unwrap_or(BigUint::ZERO);
 }
 
-fn get_combinations(
-  containers: &Vec<u32>,
-  inventory: &Vec<u32>,
-  curr_size: u32, 
-  total_size: u32) -> Vec<Vec<u32>> 
-{
-  if total_size == curr_size { return vec![inventory.clone()] }
-
-  let mut combinations: Vec<Vec<u32>> = Vec::new();
-  let mut next_containers: Vec<u32> = containers.clone();
-  for i in 0..containers.len() {
-    if curr_size + containers[i] > total_size { break }
-
-    let next_size = curr_size + containers[i];
-    let mut next_inventory = inventory.clone();
-    next_inventory.push(containers[i]);
-    next_containers.remove(0);
-    
-    let curr_combinations 
-      = get_combinations(&next_containers, &next_inventory, next_size, total_size);
-
-    curr_combinations.iter().for_each(|comb| combinations.push(comb.clone()));
-  }
+fn minimum_containers_needed(ways_by_containers: &[BigUint]) -> Option<usize> {
+  return ways_by_containers.iter().position(|ways| *ways != BigUint::ZERO);
+}
 
-  return combinations;
+/*
+  Walks every combination that fills the target, optionally with an exact
+  number of containers. fits[index][count][size] records whether the
+  containers from `index` on can still make up `size` (with `count` of them
+  when the count is fixed), so the walk never enters a branch without a
+  solution and each `next` costs at most one pass down the containers.
+*/
+struct Combinations<'a> {
+  containers: &'a [usize],
+  exact_count: bool,
+  fits: Vec<Vec<Vec<bool>>>,
+  chosen: Vec<usize>,
+  frames: Vec<(usize, usize, usize, usize)>
 }
 
-fn count_combinations_that_fit(containers: &Vec<u32>, total_size: u32) -> u32 {
-  return count_combinations(containers, 0, total_size);
+impl<'a> Combinations<'a> {
+  fn new(containers: &'a [usize], total_size: usize, count: Option<usize>) -> Combinations<'a> {
+    let n = containers.len();
+    let counts = if count.is_some() { n + 1 } else { 1 };
+    let mut fits = vec![vec![vec![false; total_size + 1]; counts]; n + 1];
+    fits[n][0][0] = true;
+    for index in (0..n).rev() {
+      for k in 0..counts {
+        for size in 0..=total_size {
+          let skip = fits[index + 1][k][size];
+          let take = size >= containers[index] && match count {
+            Some(_) => k > 0 && fits[index + 1][k - 1][size - containers[index]],
+            None => fits[index + 1][k][size - containers[index]]
+          };
+          fits[index][k][size] = skip || take;
+        }
+      }
+    }
+
+    let k = count.unwrap_or(0);
+    let frames = if k < counts && fits[0][k][total_size] { vec![(0, total_size, k, 0)] } else { vec![] };
+    return Combinations{containers, exact_count: count.is_some(), fits, chosen: Vec::new(), frames};
+  }
 }
 
-fn count_ways_to_fill_minimum_containers(containers: &Vec<u32>, total_size: u32) -> usize {
-  let inventory: Vec<u32> = Vec::new();
-  let combinations 
-    = get_combinations(containers, &inventory, 0, total_size);
+impl<'a> Iterator for Combinations<'a> {
+  type Item = Vec<usize>;
+
+  fn next(&mut self) -> Option<Vec<usize>> {
+    let (mut index, mut size, mut k, chosen_len) = self.frames.pop()?;
+    self.chosen.truncate(chosen_len);
+    while index < self.containers.len() {
+      let container = self.containers[index];
+      let next_k = if self.exact_count { k.wrapping_sub(1) } else { k };
+      let can_take = size >= container && next_k < self.fits[index + 1].len()
+        && self.fits[index + 1][next_k][size - container];
+      if can_take {
+        if self.fits[index + 1][k][size] { self.frames.push((index + 1, size, k, self.chosen.len())); }
+        self.chosen.push(container);
+        size -= container;
+        k = next_k;
+      }
+      index += 1;
+    }
+    return Some(self.chosen.clone());
+  }
+}
 
-  let minimum_containers_needed = combinations
-    .iter()
-    .map(|comb| comb.len())
-    .min()
-    .unwrap();
+fn parse_containers(contents: &str) -> Vec<usize> {
+  let mut containers = contents
+    .lines()
+    .filter(|line| !line.is_empty())
+    .map(|container| container.parse::<usize>().unwrap())
+    .collect::<Vec<usize>>();
+  containers.sort();
+  return containers;
+}
 
-  let ways_to_use_min_containers = combinations
-    .iter()
-    .filter(|comb| comb.len() == minimum_containers_needed)
-    .collect::<Vec<&Vec<u32>>>()
-    .len();
+fn solve(contents: &str, ctx: &Context, total_size: usize) -> Result<Vec<Answer>, Cancelled> {
+  let containers = parse_containers(contents);
+  let ways_by_containers = count_ways_by_containers(&containers, total_size, ctx)?;
 
-  return ways_to_use_min_containers;
+  return Ok(vec![
+    BigInt::from(count_combinations_that_fit(&ways_by_containers)).into(),
+    BigInt::from(count_ways_to_fill_minimum_containers(&ways_by_containers)).into()
+  ]);
 }
 
 fn main() {
-  let contents = fs::read_to_string("./data/q17.txt")
-    .expect("Should have been able to read file");
-
-  let mut containers = contents
-    .split("\n")
-    .map(|container| container.parse::<u32>().unwrap())
-    .collect::<Vec<u32>>();
-  containers.sort();
+  let args = Args::parse();
+  let total_size = args.value("--target").map_or(150, |size| size.parse::<usize>().unwrap());
+
+  if let Some(limit) = args.value("--list") {
+    let limit = limit.parse::<usize>().unwrap();
+    let contents = fs::read_to_string("./data/q17.txt")
+      .expect("Should have been able to read file");
+    let containers = parse_containers(&contents);
+    let ways_by_containers = count_ways_by_containers(&containers, total_size, &Context::new()).unwrap();
+    let count = if args.flag("--any-count") { None } else { minimum_containers_needed(&ways_by_containers) };
+    for combination in Combinations::new(&containers, total_size, count).take(limit) {
+      println!("{:?}", combination);
+    }
+    return;
+  }
 
-  println!("Part 1: {}", count_combinations_that_fit(&containers, 150));
-  println!("Part 2: {}", count_ways_to_fill_minimum_containers(&containers, 150));
-}
\ No newline at end of file
+  runner::run("q17", |contents, ctx| solve(contents, ctx, total_size));
+}
@@ -1,120 +1,328 @@
 use std::fs;
+use aoc_common::cli::Args;
 
-fn count_on_neighbours(grid: &Vec<Vec<char>>, i: isize, j: isize) -> u32 {
-  let mut on_neighbours: u32 = 0;
-  let directions: Vec<(isize, isize)> = vec![
-    (-1, -1), (-1, 0), (-1, 1),
-    ( 0, -1),          ( 0, 1),
-    ( 1, -1), ( 1, 0), ( 1, 1)
-  ];
-
-  for (di, dj) in directions.iter() {
-    let next_i = i + di;
-    let next_j = j + dj;
-    if next_i >= 0 && next_i < grid.len() as isize && 
-      next_j >= 0 && next_j < grid[0].len() as isize 
-    {
-      if grid[next_i as usize][next_j as usize] == '#' { on_neighbours += 1 }
-    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rule {
+  birth: u16,
+  survival: u16
+}
+
+impl Rule {
+  /* Accepts `B3/S23` as well as the older survival-first `23/3`. */
+  fn parse(rulestring: &str) -> Rule {
+    let counts = |digits: &str| digits.chars().fold(0_u16, |mask, digit| {
+      let count = digit.to_digit(10).filter(|count| *count <= 8)
+        .unwrap_or_else(|| panic!("Bad neighbour count {} in rule {}", digit, rulestring));
+      mask | (1 << count)
+    });
+
+    let (first, second) = rulestring.split_once('/')
+      .unwrap_or_else(|| panic!("Expected a rule like B3/S23 but got {}", rulestring));
+    let (first, second) = (first.to_ascii_uppercase(), second.to_ascii_uppercase());
+    return match (first.strip_prefix('B'), second.strip_prefix('S'), first.strip_prefix('S'), second.strip_prefix('B')) {
+      (Some(birth), Some(survival), _, _) | (_, _, Some(survival), Some(birth)) =>
+        Rule{birth: counts(birth), survival: counts(survival)},
+      _ => Rule{birth: counts(&second), survival: counts(&first)}
+    };
   }
 
-  return on_neighbours;
+  fn to_rulestring(self) -> String {
+    let digits = |mask: u16| (0..=8).filter(|n| mask & (1 << n) != 0).map(|n| n.to_string()).collect::<String>();
+    return format!("B{}/S{}", digits(self.birth), digits(self.survival));
+  }
 }
 
-fn animate(grid: &Vec<Vec<char>>) -> Vec<Vec<char>> {
-  let curr_grid = grid.clone();
-  let mut next_grid: Vec<Vec<char>> = Vec::new();
-
-  for i in 0..curr_grid.len() {
-    let mut next_row: Vec<char> = Vec::new();
-
-    for j in 0..curr_grid[0].len() {
-      let on_neighbours = count_on_neighbours(&curr_grid, i as isize, j as isize);
-      match curr_grid[i][j] {
-        '#' => {
-          if on_neighbours == 2 || on_neighbours == 3 {
-            next_row.push('#');
-          } else {
-            next_row.push('.');
-          }
-        },
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edges {
+  Bounded,
+  Toroidal
+}
 
-        '.' => {
-          if on_neighbours == 3 {
-            next_row.push('#');
-          } else {
-            next_row.push('.');
-          }
-        }, 
+/*
+  Each row is packed into 64-bit words, bit `c % 64` of word `c / 64` holding
+  column c. Bits past the last column are always kept clear.
+*/
+#[derive(Clone, Debug, PartialEq)]
+struct Board {
+  width: usize,
+  height: usize,
+  words: usize,
+  cells: Vec<u64>
+}
+
+impl Board {
+  fn new(width: usize, height: usize) -> Board {
+    let words = width.div_ceil(64).max(1);
+    return Board{width, height, words, cells: vec![0; words * height]};
+  }
+
+  fn get(&self, x: usize, y: usize) -> bool {
+    return self.cells[y * self.words + x / 64] >> (x % 64) & 1 == 1;
+  }
+
+  fn set(&mut self, x: usize, y: usize, alive: bool) {
+    let word = &mut self.cells[y * self.words + x / 64];
+    if alive { *word |= 1 << (x % 64); } else { *word &= !(1 << (x % 64)); }
+  }
+
+  fn row(&self, y: usize) -> &[u64] {
+    return &self.cells[y * self.words..(y + 1) * self.words];
+  }
+
+  fn last_word_mask(&self) -> u64 {
+    return match self.width % 64 {
+      0 if self.width > 0 => u64::MAX,
+      0 => 0,
+      bits => (1 << bits) - 1
+    };
+  }
+
+  fn count_alive(&self) -> usize {
+    return self.cells.iter().map(|word| word.count_ones() as usize).sum();
+  }
 
-        _ => println!("Character in grid not matched: {}", curr_grid[i][j])
+  fn from_grid(contents: &str) -> Board {
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.is_empty()).collect();
+    let mut board = Board::new(lines.iter().map(|line| line.len()).max().unwrap_or(0), lines.len());
+    for (y, line) in lines.iter().enumerate() {
+      for (x, light) in line.chars().enumerate() {
+        match light {
+          '#' => board.set(x, y, true),
+          '.' => {},
+          _ => println!("Character in grid not matched: {}", light)
+        }
       }
     }
+    return board;
+  }
+
+  fn to_grid(&self) -> String {
+    let mut grid = String::new();
+    for y in 0..self.height {
+      for x in 0..self.width { grid.push(if self.get(x, y) { '#' } else { '.' }); }
+      grid.push('\n');
+    }
+    return grid;
+  }
+}
 
-    next_grid.push(next_row);
+/* The rule, if the header names one, alongside the pattern. */
+fn read_rle(contents: &str) -> (Board, Option<Rule>) {
+  let mut lines = contents.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#'));
+  let header = lines.next().unwrap_or_else(|| panic!("RLE pattern is missing its header"));
+  let mut size = (0, 0);
+  let mut rule = None;
+  for field in header.split(',') {
+    let (key, value) = field.split_once('=').unwrap_or_else(|| panic!("Bad RLE header field: {}", field));
+    match key.trim() {
+      "x" => size.0 = value.trim().parse::<usize>().unwrap(),
+      "y" => size.1 = value.trim().parse::<usize>().unwrap(),
+      "rule" => rule = Some(Rule::parse(value.trim())),
+      _ => {}
+    }
   }
 
-  return next_grid;
+  let mut board = Board::new(size.0, size.1);
+  let (mut x, mut y, mut run) = (0, 0, 0);
+  'body: for line in lines {
+    for tag in line.chars() {
+      match tag {
+        '0'..='9' => { run = run * 10 + tag.to_digit(10).unwrap() as usize; continue; },
+        'b' | '.' => x += run.max(1),
+        '$' => { y += run.max(1); x = 0; },
+        '!' => break 'body,
+        _ if tag.is_whitespace() => continue,
+        _ => {
+          for _ in 0..run.max(1) {
+            if x < board.width && y < board.height { board.set(x, y, true); }
+            x += 1;
+          }
+        }
+      }
+      run = 0;
+    }
+  }
+  return (board, rule);
 }
 
-fn animate_and_count_lights_on(grid: &Vec<Vec<char>>, reps: u32) -> usize {
-  let mut final_grid = grid.clone();
-  for _ in 0..reps {
-    final_grid = animate(&final_grid);
-  }
-
-  return final_grid
-    .iter()
-    .map(|row| row
-      .iter()
-      .filter(|light| **light == '#')
-      .collect::<Vec<&char>>()
-      .len()
-    )
-    .sum();
+fn push_run(body: &mut String, count: usize, tag: char) {
+  if count > 1 { body.push_str(&count.to_string()); }
+  if count > 0 { body.push(tag); }
 }
 
-fn turn_corners_on(grid: &Vec<Vec<char>>) -> Vec<Vec<char>> {
-  let mut curr_grid = grid.clone();
-  let length = curr_grid.len();
-  let width = curr_grid[0].len();
+fn write_rle(board: &Board, rule: Rule) -> String {
+  let mut body = String::new();
+
+  let mut pending_rows = 0;
+  for y in 0..board.height {
+    let last_alive = (0..board.width).rev().find(|x| board.get(*x, y));
+    let last_alive = match last_alive {
+      Some(last_alive) => last_alive,
+      None => { pending_rows += 1; continue; }
+    };
+    push_run(&mut body, pending_rows, '$');
+    pending_rows = 1;
 
-  curr_grid[0][0] = '#';
-  curr_grid[0][width - 1] = '#';
-  curr_grid[length - 1][0] = '#';
-  curr_grid[length - 1][width - 1] = '#';
+    let mut x = 0;
+    while x <= last_alive {
+      let alive = board.get(x, y);
+      let start = x;
+      while x <= last_alive && board.get(x, y) == alive { x += 1; }
+      push_run(&mut body, x - start, if alive { 'o' } else { 'b' });
+    }
+  }
+  body.push('!');
+
+  let mut rle = format!("x = {}, y = {}, rule = {}\n", board.width, board.height, rule.to_rulestring());
+  let mut line_length = 0;
+  for (i, c) in body.char_indices() {
+    if line_length >= 70 && !body.as_bytes()[i - 1].is_ascii_digit() {
+      rle.push('\n');
+      line_length = 0;
+    }
+    rle.push(c);
+    line_length += 1;
+  }
+  rle.push('\n');
+  return rle;
+}
 
-  return curr_grid;
+struct Life {
+  rule: Rule,
+  edges: Edges,
+  pinned: Vec<(usize, usize, bool)>
 }
 
-fn animate_and_count_lights_on_with_corners_stuck(grid: &Vec<Vec<char>>, reps: u32) -> usize {
-  let mut final_grid = grid.clone();
-  for _ in 0..reps {
-    final_grid = turn_corners_on(&final_grid);
-    final_grid = animate(&final_grid);
+impl Life {
+  fn pin(&self, board: &mut Board) {
+    for (x, y, alive) in self.pinned.iter() { board.set(*x, *y, *alive); }
+  }
+
+  fn neighbour_row<'a>(&self, board: &'a Board, y: usize, offset: isize) -> Option<&'a [u64]> {
+    let neighbour = y as isize + offset;
+    if neighbour >= 0 && (neighbour as usize) < board.height { return Some(board.row(neighbour as usize)); }
+    return match self.edges {
+      Edges::Toroidal => Some(board.row(neighbour.rem_euclid(board.height as isize) as usize)),
+      Edges::Bounded => None
+    };
+  }
+
+  /* Column c - 1 moved into bit c (`west`) and column c + 1 into bit c (`east`). */
+  fn shifted(&self, board: &Board, row: &[u64], word: usize) -> (u64, u64) {
+    let wrap = self.edges == Edges::Toroidal;
+    let last_column = board.width - 1;
+    let column = |c: usize| row[c / 64] >> (c % 64) & 1;
+
+    let mut west = row[word] << 1;
+    if word > 0 { west |= row[word - 1] >> 63; } else if wrap { west |= column(last_column); }
+
+    let mut east = row[word] >> 1;
+    if word + 1 < board.words { east |= (row[word + 1] & 1) << 63; }
+    if wrap && word == last_column / 64 { east |= column(0) << (last_column % 64); }
+    return (west, east);
+  }
+
+  /*
+    Counts all eight neighbours of 64 cells at once: each neighbour word is
+    added into a four-bit counter held as four bit-planes, then the rule picks
+    out the counts that give birth or survival.
+  */
+  fn step(&self, board: &Board) -> Board {
+    let mut next = Board::new(board.width, board.height);
+    if board.width == 0 { return next; }
+    let last_mask = board.last_word_mask();
+
+    for y in 0..board.height {
+      let rows = [self.neighbour_row(board, y, -1), Some(board.row(y)), self.neighbour_row(board, y, 1)];
+      for word in 0..board.words {
+        let mut planes = [0_u64; 4];
+        let mut add = |bits: u64| {
+          let mut carry = bits;
+          for plane in planes.iter_mut() {
+            let overflow = *plane & carry;
+            *plane ^= carry;
+            carry = overflow;
+          }
+        };
+
+        for (i, row) in rows.iter().enumerate() {
+          let row = match row {
+            Some(row) => row,
+            None => continue
+          };
+          let (west, east) = self.shifted(board, row, word);
+          add(west);
+          add(east);
+          if i != 1 { add(row[word]); }
+        }
+
+        let alive = board.row(y)[word];
+        let mut born = 0;
+        let mut survives = 0;
+        for count in 0..=8_u16 {
+          let born_here = self.rule.birth & (1 << count) != 0;
+          let survives_here = self.rule.survival & (1 << count) != 0;
+          if !born_here && !survives_here { continue; }
+          let matches = planes.iter().enumerate()
+            .fold(u64::MAX, |acc, (bit, plane)| acc & if count >> bit & 1 == 1 { *plane } else { !*plane });
+          if born_here { born |= matches; }
+          if survives_here { survives |= matches; }
+        }
+
+        let mut cells = (alive & survives) | (!alive & born);
+        if word == board.words - 1 { cells &= last_mask; }
+        next.cells[y * board.words + word] = cells;
+      }
+    }
+
+    self.pin(&mut next);
+    return next;
   }
 
-  final_grid = turn_corners_on(&final_grid);
+  fn run(&self, board: &Board, generations: u32) -> Board {
+    let mut board = board.clone();
+    self.pin(&mut board);
+    for _ in 0..generations {
+      board = self.step(&board);
+    }
+    return board;
+  }
+}
 
-  return final_grid
-    .iter()
-    .map(|row| row
-      .iter()
-      .filter(|light| **light == '#')
-      .collect::<Vec<&char>>()
-      .len()
-    )
-    .sum();
+fn parse_pin(value: &str, board: &Board) -> (usize, usize, bool) {
+  let (x, y) = value.split_once(',').unwrap_or_else(|| panic!("Expected x,y but got {}", value));
+  let (x, y) = (x.parse::<usize>().unwrap(), y.parse::<usize>().unwrap());
+  if x >= board.width || y >= board.height {
+    panic!("Pin {},{} is off the {}x{} board", x, y, board.width, board.height);
+  }
+  return (x, y, true);
 }
 
 fn main() {
-  let contents = fs::read_to_string("./data/q18.txt")
-    .expect("Should have been able to read file");
-  let grid: Vec<Vec<char>> = contents
-    .split("\n")
-    .map(|line| line.chars().collect::<Vec<char>>())
-    .collect::<Vec<Vec<char>>>();
-
-  println!("Part 1: {}", animate_and_count_lights_on(&grid, 100));
-  println!("Part 2: {}", animate_and_count_lights_on_with_corners_stuck(&grid, 100));
-}
\ No newline at end of file
+  let args = Args::parse();
+  let (board, pattern_rule) = match args.value("--rle") {
+    Some(path) => read_rle(&fs::read_to_string(path).expect("Should have been able to read file")),
+    None => (Board::from_grid(&fs::read_to_string("./data/q18.txt")
+      .expect("Should have been able to read file")), None)
+  };
+
+  let rule = args.value("--rule").map(Rule::parse).or(pattern_rule).unwrap_or(Rule::parse("B3/S23"));
+  let edges = if args.flag("--torus") { Edges::Toroidal } else { Edges::Bounded };
+  let generations = args.value("--generations").map_or(100, |n| n.parse::<u32>().unwrap());
+  let pinned: Vec<(usize, usize, bool)> = args.values("--pin").iter().map(|value| parse_pin(value, &board)).collect();
+
+  let life = Life{rule, edges, pinned: pinned.clone()};
+  let lights = life.run(&board, generations);
+  println!("Part 1: {}", lights.count_alive());
+
+  let mut stuck = pinned;
+  if board.width > 0 && board.height > 0 {
+    let (right, bottom) = (board.width - 1, board.height - 1);
+    stuck.extend([(0, 0, true), (right, 0, true), (0, bottom, true), (right, bottom, true)]);
+  }
+  let stuck_life = Life{rule, edges, pinned: stuck};
+  println!("Part 2: {}", stuck_life.run(&board, generations).count_alive());
+
+  if args.flag("--write-rle") { print!("{}", write_rle(&lights, rule)); }
+  if args.flag("--show") { print!("{}", lights.to_grid()); }
+}
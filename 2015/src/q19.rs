@@ -1,4 +1,5 @@
-use std::{fs, collections::{HashMap, HashSet}};
+use std::{fs, fmt, collections::{HashMap, HashSet}};
+use aoc_common::cli::Args;
 
 fn replace_molecule(
   molecule: &Vec<char>, 
@@ -57,68 +58,240 @@ fn count_distinct_molecules_after_replacement(
   return distinct_molcules.len();
 }
 
-fn try_reduce(
-  molecule: &String, 
-  longest_match: usize, 
-  rules: &HashMap<String, String>) -> (u32, String)
-{
-  let mut reductions: u32 = 0;
-  let mut molecule_vec: Vec<char> = molecule.chars().collect();
-  let mut index: isize = (molecule_vec.len() - 1) as isize;
-
-  while index >= 0 {
-    let mut window = String::new();
-    let mut window_index = index;
-    window.push(molecule_vec[window_index as usize]);
-
-    while window_index + 1 < molecule_vec.len() as isize && window.len() < longest_match && 
-      !rules.contains_key(&window) 
-    {
-      window_index += 1;
-      window.push(molecule_vec[window_index as usize]);
+#[derive(Debug, PartialEq)]
+enum FabricationError {
+  UnknownElement(String),
+  NotDerivable
+}
+
+impl fmt::Display for FabricationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FabricationError::UnknownElement(element) => write!(f, "no rule produces {}", element),
+      FabricationError::NotDerivable => write!(f, "the molecule cannot be made from e")
+    }
+  }
+}
+
+/* An element is a capital followed by any lower-case letters; `e` stands alone. */
+fn tokenize(molecule: &str) -> Vec<String> {
+  let mut tokens: Vec<String> = Vec::new();
+  for letter in molecule.chars() {
+    match tokens.last_mut() {
+      Some(token) if letter.is_ascii_lowercase() => token.push(letter),
+      _ => tokens.push(letter.to_string())
+    }
+  }
+  return tokens;
+}
+
+struct Binary {
+  lhs: usize,
+  left: usize,
+  right: usize,
+  cost: u32
+}
+
+/*
+  The replacements as a context-free grammar. Single-element rules stay unary
+  and every rule of three or more elements is split into a chain of binary
+  rules through helper symbols; only the first link costs a step, so a parse
+  of the molecule costs exactly the number of replacements in the derivation
+  it describes.
+*/
+struct Grammar {
+  symbols: Vec<String>,
+  rules: Vec<(usize, Vec<usize>)>,
+  binary: Vec<Binary>,
+  unit: Vec<(usize, usize)>
+}
+
+impl Grammar {
+  fn new(rules: &[(String, String)]) -> Grammar {
+    let mut grammar = Grammar{symbols: Vec::new(), rules: Vec::new(), binary: Vec::new(), unit: Vec::new()};
+    for (from, to) in rules.iter() {
+      let lhs = grammar.symbol(from);
+      let rhs = tokenize(to).iter().map(|element| grammar.symbol(element)).collect::<Vec<usize>>();
+      if rhs.is_empty() { panic!("Rule {} => has nothing to replace {} with", from, from); }
+      grammar.rules.push((lhs, rhs));
     }
 
-    if rules.contains_key(&window) {
-      let replacement = rules.get(&window).unwrap();
-      molecule_vec = replace_molecule(&molecule_vec, &window, index as usize, replacement)
-        .chars()
-        .collect();
-      reductions += 1;
+    for r in 0..grammar.rules.len() {
+      let (lhs, rhs) = grammar.rules[r].clone();
+      if rhs.len() == 1 {
+        grammar.unit.push((lhs, rhs[0]));
+        continue;
+      }
+      let mut head = lhs;
+      let mut cost = 1;
+      for (i, left) in rhs[..rhs.len() - 2].iter().enumerate() {
+        let helper = grammar.helper(r, i);
+        grammar.binary.push(Binary{lhs: head, left: *left, right: helper, cost});
+        head = helper;
+        cost = 0;
+      }
+      grammar.binary.push(Binary{lhs: head, left: rhs[rhs.len() - 2], right: rhs[rhs.len() - 1], cost});
     }
+    return grammar;
+  }
+
+  fn symbol(&mut self, name: &str) -> usize {
+    if let Some(index) = self.symbols.iter().position(|s| s == name) { return index; }
+    self.symbols.push(name.to_string());
+    return self.symbols.len() - 1;
+  }
+
+  fn helper(&mut self, rule: usize, link: usize) -> usize {
+    return self.symbol(&format!("<{}.{}>", rule, link));
+  }
+
+  fn is_helper(&self, symbol: usize) -> bool {
+    return self.symbols[symbol].starts_with('<');
+  }
 
-    index -= 1;
+  fn rule_name(&self, rule: usize) -> String {
+    let (lhs, rhs) = &self.rules[rule];
+    let rhs = rhs.iter().map(|s| self.symbols[*s].as_str()).collect::<String>();
+    return format!("{} => {}", self.symbols[*lhs], rhs);
   }
+}
 
-  return (reductions, molecule_vec.iter().collect());
+#[derive(Debug)]
+enum Node {
+  Element(usize),
+  Replace(usize, Vec<Node>)
 }
 
-fn molecule_fabrication(target: &String, rules: &HashMap<String, Vec<String>>) -> u32 {
-  let mut steps: u32 = 0;
-  let mut curr_molecule = target.clone();
-  let mut reverse_rules: HashMap<String, String> = HashMap::new();
-  let mut longest_match: usize = 0;
-  for (k, v) in rules.iter() {
-    for result in v.iter() {
-      if result.len() > longest_match { longest_match = result.len() }
-      reverse_rules.insert(result.clone(), k.clone());
+/*
+  Weighted CYK: cost[span][symbol] is the fewest replacements that turn
+  `symbol` into exactly the elements of `span`. Spans are filled shortest
+  first, and single-element rules are relaxed within a span until nothing
+  improves since they don't change its length.
+*/
+struct Chart<'a> {
+  grammar: &'a Grammar,
+  tokens: Vec<usize>,
+  cost: Vec<u32>
+}
+
+impl<'a> Chart<'a> {
+  fn cell(&self, i: usize, j: usize) -> usize {
+    return (j * (j - 1) / 2 + i) * self.grammar.symbols.len();
+  }
+
+  fn get(&self, i: usize, j: usize, symbol: usize) -> u32 {
+    return self.cost[self.cell(i, j) + symbol];
+  }
+
+  fn parse(grammar: &'a Grammar, molecule: &str) -> Result<Chart<'a>, FabricationError> {
+    let tokens = tokenize(molecule).iter().map(|element| grammar.symbols.iter().position(|s| s == element)
+      .ok_or_else(|| FabricationError::UnknownElement(element.clone())))
+      .collect::<Result<Vec<usize>, FabricationError>>()?;
+    let n = tokens.len();
+    let mut chart = Chart{grammar, tokens, cost: vec![u32::MAX; n * (n + 1) / 2 * grammar.symbols.len()]};
+
+    for length in 1..=n {
+      for i in 0..=(n - length) {
+        let j = i + length;
+        let cell = chart.cell(i, j);
+        if length == 1 { chart.cost[cell + chart.tokens[i]] = 0; }
+        for rule in grammar.binary.iter() {
+          for k in (i + 1)..j {
+            let (left, right) = (chart.get(i, k, rule.left), chart.get(k, j, rule.right));
+            if left == u32::MAX || right == u32::MAX { continue; }
+            let total = left + right + rule.cost;
+            if total < chart.cost[cell + rule.lhs] { chart.cost[cell + rule.lhs] = total; }
+          }
+        }
+        let mut improved = true;
+        while improved {
+          improved = false;
+          for (lhs, rhs) in grammar.unit.iter() {
+            let from = chart.cost[cell + rhs];
+            if from != u32::MAX && from + 1 < chart.cost[cell + lhs] {
+              chart.cost[cell + lhs] = from + 1;
+              improved = true;
+            }
+          }
+        }
+      }
+    }
+    return Ok(chart);
+  }
+
+  fn build(&self, i: usize, j: usize, symbol: usize) -> Node {
+    let target = self.get(i, j, symbol);
+    if j == i + 1 && self.tokens[i] == symbol && target == 0 { return Node::Element(symbol); }
+
+    for (r, (lhs, rhs)) in self.grammar.rules.iter().enumerate() {
+      if *lhs != symbol || rhs.len() != 1 { continue; }
+      let from = self.get(i, j, rhs[0]);
+      if from != u32::MAX && from + 1 == target { return Node::Replace(r, vec![self.build(i, j, rhs[0])]); }
+    }
+
+    let parts = self.build_parts(i, j, symbol, target);
+    let rule = self.grammar.rules.iter().position(|(lhs, rhs)| *lhs == symbol && rhs.len() == parts.len()
+      && rhs.iter().zip(parts.iter()).all(|(s, part)| *s == self.top_symbol(part))).unwrap();
+    return Node::Replace(rule, parts);
+  }
+
+  fn build_parts(&self, i: usize, j: usize, symbol: usize, target: u32) -> Vec<Node> {
+    for rule in self.grammar.binary.iter().filter(|rule| rule.lhs == symbol) {
+      for k in (i + 1)..j {
+        let (left, right) = (self.get(i, k, rule.left), self.get(k, j, rule.right));
+        if left == u32::MAX || right == u32::MAX || left + right + rule.cost != target { continue; }
+        let mut parts = vec![self.build(i, k, rule.left)];
+        if self.grammar.is_helper(rule.right) {
+          parts.extend(self.build_parts(k, j, rule.right, right));
+        } else {
+          parts.push(self.build(k, j, rule.right));
+        }
+        return parts;
+      }
     }
+    unreachable!("every finite cost in the chart comes from some rule");
   }
 
-  while curr_molecule.as_str() != "e" {
-    let (reductions, next_molecule) 
-      = try_reduce(&curr_molecule, longest_match, &reverse_rules);
-    steps += reductions;
-    curr_molecule = next_molecule;
+  fn top_symbol(&self, node: &Node) -> usize {
+    return match node {
+      Node::Element(symbol) => *symbol,
+      Node::Replace(rule, _) => self.grammar.rules[*rule].0
+    };
   }
+}
+
+struct Derivation {
+  steps: usize,
+  /* Each replacement applied, leftmost first, with the molecule it leaves. */
+  sequence: Vec<(usize, String)>
+}
+
+fn molecule_fabrication(grammar: &Grammar, target: &str) -> Result<Derivation, FabricationError> {
+  let chart = Chart::parse(grammar, target)?;
+  let n = chart.tokens.len();
+  let start = grammar.symbols.iter().position(|s| s == "e").ok_or(FabricationError::NotDerivable)?;
+  if n == 0 || chart.get(0, n, start) == u32::MAX { return Err(FabricationError::NotDerivable); }
 
-  return steps;
+  let root = chart.build(0, n, start);
+  let mut form: Vec<&Node> = vec![&root];
+  let mut sequence = Vec::new();
+  while let Some(position) = form.iter().position(|node| matches!(node, Node::Replace(_, _))) {
+    if let Node::Replace(rule, children) = form[position] {
+      form.splice(position..=position, children.iter());
+      let molecule = form.iter().map(|node| grammar.symbols[chart.top_symbol(node)].as_str()).collect::<String>();
+      sequence.push((*rule, molecule));
+    }
+  }
+  return Ok(Derivation{steps: chart.get(0, n, start) as usize, sequence});
 }
 
 fn main() {
+  let args = Args::parse();
   let contents = fs::read_to_string("./data/q19.txt")
     .expect("Should have been able to read file");
   let mut initial_molecule: String = String::new();
   let mut rules: HashMap<String, Vec<String>> = HashMap::new();
+  let mut rule_list: Vec<(String, String)> = Vec::new();
   let mut parsing_rules: bool = true;
 
   for line in contents.split("\n") {
@@ -136,11 +309,25 @@ fn main() {
       } else {
         rules.insert(rule[0].clone(), vec![rule[1].clone()]);
       }
+      rule_list.push((rule[0].clone(), rule[1].clone()));
     } else {
       initial_molecule = line.to_string();
     }
   }
 
   println!("Part 1: {}", count_distinct_molecules_after_replacement(&initial_molecule, &rules));
-  println!("Part 2: {}", molecule_fabrication(&initial_molecule, &rules));
-}
\ No newline at end of file
+
+  let grammar = Grammar::new(&rule_list);
+  let target = args.value("--target").unwrap_or(&initial_molecule);
+  match molecule_fabrication(&grammar, target) {
+    Ok(derivation) => {
+      println!("Part 2: {}", derivation.steps);
+      if args.flag("--derivation") {
+        for (step, (rule, molecule)) in derivation.sequence.iter().enumerate() {
+          println!("  {:>3}. {:<12} {}", step + 1, grammar.rule_name(*rule), molecule);
+        }
+      }
+    },
+    Err(e) => println!("Part 2: {}", e)
+  }
+}
@@ -1,51 +1,144 @@
-use std::fs;
-
-fn get_lowest_house_exceeding_limit(limit: &u32) -> u32 {
-  let house_number_limit: u32 = limit / 10;
-  let mut factors: Vec<Vec<u32>> = vec![vec![]; house_number_limit as usize];
-  for factor in 1..house_number_limit + 1 {
-    for index in (factor..house_number_limit + 1).step_by(factor as usize) {
-      factors[index as usize - 1].push(factor);
-    }
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
+
+const SMALL_PRIMES: [u64; 15] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+const PROGRESS_EVERY: u64 = 1 << 14;
+
+/* Elf e brings `presents_per_elf * e` to houses e, 2e, 3e, ... and stops after `max_houses`. */
+#[derive(Clone, Copy, Debug)]
+struct DeliveryRule {
+  presents_per_elf: u64,
+  max_houses: Option<u64>
+}
+
+impl DeliveryRule {
+  fn delivers(&self, elf: u64, house: u64) -> bool {
+    return self.max_houses.is_none_or(|max| house / elf <= max);
   }
 
-  let mut house_number = 0;
-  for i in 0..factors.len() {
-    if factors[i].iter().sum::<u32>() >= house_number_limit { 
-      house_number = (i + 1) as u32;
-      break;
+  fn last_house(&self, elf: u64, bound: u64) -> u64 {
+    return self.max_houses.map_or(bound, |max| bound.min(elf.saturating_mul(max)));
+  }
+
+  /* The presents a house gets, from its factorisation as (prime, exponent) pairs. */
+  fn presents_at(&self, house: u64, factors: &[(u64, u32)]) -> u64 {
+    let mut divisors = vec![1_u64];
+    for (prime, exponent) in factors.iter() {
+      let mut next = Vec::with_capacity(divisors.len() * (*exponent as usize + 1));
+      for divisor in divisors.iter() {
+        let mut power = 1;
+        for _ in 0..=*exponent {
+          next.push(divisor * power);
+          power *= prime;
+        }
+      }
+      divisors = next;
     }
+    return divisors.iter().filter(|elf| self.delivers(**elf, house)).sum::<u64>() * self.presents_per_elf;
+  }
+}
+
+/*
+  Houses that get the most presents for their number are the highly abundant
+  ones, and those are built from the smallest primes with exponents that never
+  increase. Trying every such number up to the trivial bound (elf n alone
+  brings enough to house n) finds a house that is already enough, which is
+  usually within a few percent of the answer and so bounds the sieve.
+*/
+fn upper_bound(limit: u64, rule: DeliveryRule) -> u64 {
+  let trivial = limit.div_ceil(rule.presents_per_elf).max(1);
+  let mut best = trivial;
+  let mut factors: Vec<(u64, u32)> = Vec::new();
+  search_abundant(limit, rule, 1, 0, u32::MAX, &mut factors, &mut best);
+  return best;
+}
+
+fn search_abundant(limit: u64, rule: DeliveryRule, house: u64, prime_index: usize, max_exponent: u32,
+  factors: &mut Vec<(u64, u32)>, best: &mut u64)
+{
+  if rule.presents_at(house, factors) >= limit {
+    *best = (*best).min(house);
+    return;
+  }
+  if prime_index == SMALL_PRIMES.len() { return; }
+
+  let prime = SMALL_PRIMES[prime_index];
+  let mut next = house;
+  for exponent in 1..=max_exponent {
+    next = match next.checked_mul(prime) {
+      Some(next) if next < *best => next,
+      _ => break
+    };
+    factors.push((prime, exponent));
+    search_abundant(limit, rule, next, prime_index + 1, exponent, factors, best);
+    factors.pop();
   }
-  return house_number;
 }
 
-fn get_new_lowest_house_exceeding_limit(limit: &u32) -> u32 {
-  let house_number_limit: u32 = (limit / 11) + 1;
-  let mut factors: Vec<Vec<u32>> = vec![vec![]; house_number_limit as usize];
-  for factor in 1..house_number_limit + 1 {
-    let mut delivered_to: u32 = 0;
-    for index in (factor..house_number_limit + 1).step_by(factor as usize) {
-      if delivered_to == 50 { break }
-      factors[index as usize - 1].push(factor);
-      delivered_to += 1;
+/*
+  Adds each elf's presents straight into houses `first..first + presents.len()`.
+  An elf only reaches the window if one of its multiples lands in it before it
+  runs out of houses.
+*/
+fn deliver_to_segment(presents: &mut [u64], first: u64, rule: DeliveryRule, ctx: &Context) -> Result<(), Cancelled> {
+  let last = first + presents.len() as u64 - 1;
+  let first_elf = rule.max_houses.map_or(1, |max| first.div_ceil(max).max(1));
+  for elf in first_elf..=last {
+    if elf % PROGRESS_EVERY == 0 { ctx.progress("elves delivered", elf, Some(last))?; }
+    let gift = elf * rule.presents_per_elf;
+    let mut house = first.div_ceil(elf).max(1) * elf;
+    let stop = rule.last_house(elf, last);
+    while house <= stop {
+      presents[(house - first) as usize] += gift;
+      house += elf;
     }
   }
+  return Ok(());
+}
+
+fn get_lowest_house(limit: u64, rule: DeliveryRule, segment: Option<u64>, ctx: &Context) -> Result<u64, Cancelled> {
+  let bound = upper_bound(limit, rule);
+  let segment = segment.unwrap_or(bound).max(1);
 
-  let mut house_number = 0;
-  for i in 0..factors.len() {
-    if factors[i].iter().sum::<u32>() * 11 >= *limit { 
-      house_number = (i + 1) as u32;
-      break;
+  let mut first = 1;
+  while first <= bound {
+    let length = segment.min(bound - first + 1);
+    let mut presents = vec![0_u64; length as usize];
+    deliver_to_segment(&mut presents, first, rule, ctx)?;
+    if let Some(offset) = presents.iter().position(|presents| *presents >= limit) {
+      return Ok(first + offset as u64);
     }
+    first += length;
   }
-  return house_number;
+  unreachable!("the upper bound is a house that gets enough presents");
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q20.txt")
-    .expect("Should have been able to read file");
-  let present_limit = contents.parse::<u32>().unwrap();
+fn solve(contents: &str, ctx: &Context, segment: Option<u64>, custom: Option<DeliveryRule>) -> Result<Vec<Answer>, Cancelled> {
+  let present_limit = contents.trim().parse::<u64>().unwrap();
 
-  println!("Part 1: {}", get_lowest_house_exceeding_limit(&present_limit));
-  println!("Part 2: {}", get_new_lowest_house_exceeding_limit(&present_limit));
-}
\ No newline at end of file
+  let mut answers: Vec<Answer> = vec![
+    get_lowest_house(present_limit, DeliveryRule{presents_per_elf: 10, max_houses: None}, segment, ctx)?.into(),
+    get_lowest_house(present_limit, DeliveryRule{presents_per_elf: 11, max_houses: Some(50)}, segment, ctx)?.into()
+  ];
+  if let Some(rule) = custom {
+    answers.push(get_lowest_house(present_limit, rule, segment, ctx)?.into());
+  }
+  return Ok(answers);
+}
+
+fn main() {
+  let args = Args::parse();
+  let segment = args.value("--segment").map(|size| size.parse::<u64>().unwrap());
+  let custom = args.value("--presents-per-elf").map(|presents| {
+    let rule = DeliveryRule{
+      presents_per_elf: presents.parse::<u64>().unwrap(),
+      max_houses: args.value("--max-houses").map(|max| max.parse::<u64>().unwrap())
+    };
+    if rule.presents_per_elf == 0 { panic!("--presents-per-elf must be at least 1"); }
+    if rule.max_houses == Some(0) { panic!("--max-houses must be at least 1"); }
+    rule
+  });
+  runner::run("q20", |contents, ctx| solve(contents, ctx, segment, custom));
+}
@@ -1,4 +1,39 @@
 use std::fs;
+use aoc_common::cli::Args;
+
+/* Used when there is no `./data/q21_shop.txt` and no `--shop` file. */
+const DEFAULT_SHOP: &str = "Player:
+Hit Points: 100
+Damage: 0
+Armor: 0
+
+Loadout:
+Weapons: 1-1
+Armor: 0-1
+Rings: 0-2
+
+Weapons:    Cost  Damage  Armor
+Dagger        8     4       0
+Shortsword   10     5       0
+Warhammer    25     6       0
+Longsword    40     7       0
+Greataxe     74     8       0
+
+Armor:      Cost  Damage  Armor
+Leather      13     0       1
+Chainmail    31     0       2
+Splintmail   53     0       3
+Bandedmail   75     0       4
+Platemail   102     0       5
+
+Rings:      Cost  Damage  Armor
+Damage +1    25     1       0
+Damage +2    50     2       0
+Damage +3   100     3       0
+Defense +1   20     0       1
+Defense +2   40     0       2
+Defense +3   80     0       3
+";
 
 #[derive(Debug)]
 struct Equipment {
@@ -9,127 +44,207 @@ struct Equipment {
 }
 
 #[derive(Debug)]
-struct Shop {
-  weapons: Vec<Equipment>,
-  armour: Vec<Equipment>,
-  rings: Vec<Equipment>
+struct Category {
+  name: String,
+  items: Vec<Equipment>,
+  min_picks: usize,
+  max_picks: usize
 }
 
 #[derive(Debug)]
+struct Shop {
+  player: Character,
+  categories: Vec<Category>
+}
+
+#[derive(Debug, Clone, Copy)]
 struct Character {
   hp: i32,
   dmg: i32,
   amr: i32
 }
 
-fn fight(player: &Character, boss: &Character) -> bool {
-  let mut curr_player_hp = player.hp;
-  let mut curr_boss_hp = boss.hp;
+fn parse_character(lines: &[&str]) -> Character {
+  let mut character = Character{hp: 0, dmg: 0, amr: 0};
+  for line in lines.iter() {
+    let (stat, value) = line.split_once(": ").unwrap_or_else(|| panic!("Bad stat line: {}", line));
+    let value = value.trim().parse::<i32>().unwrap();
+    match stat.trim() {
+      "Hit Points" => character.hp = value,
+      "Damage" => character.dmg = value,
+      "Armor" => character.amr = value,
+      _ => panic!("Unknown stat: {}", stat)
+    }
+  }
+  return character;
+}
+
+/* The three numbers are always the last columns, so item names may contain spaces. */
+fn parse_item(line: &str) -> Equipment {
+  let columns = line.split_whitespace().collect::<Vec<&str>>();
+  if columns.len() < 4 { panic!("Bad item line: {}", line); }
+  let numbers = columns[columns.len() - 3..].iter().map(|n| n.parse::<u32>().unwrap()).collect::<Vec<u32>>();
+  return Equipment{
+    name: columns[..columns.len() - 3].join(" "),
+    cost: numbers[0],
+    dmg: numbers[1],
+    amr: numbers[2]
+  };
+}
+
+fn parse_shop(contents: &str) -> Shop {
+  let mut player = Character{hp: 100, dmg: 0, amr: 0};
+  let mut categories: Vec<Category> = Vec::new();
+  let mut loadout: Vec<(String, usize, usize)> = Vec::new();
+
+  for section in contents.replace("\r", "").split("\n\n") {
+    let lines = section.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<&str>>();
+    let (header, body) = match lines.split_first() {
+      Some(split) => split,
+      None => continue
+    };
+    let name = header.split(':').next().unwrap().trim();
+    match name {
+      "Player" => player = parse_character(body),
+      "Loadout" => for line in body.iter() {
+        let (category, picks) = line.split_once(": ").unwrap_or_else(|| panic!("Bad loadout line: {}", line));
+        let (min, max) = picks.trim().split_once('-').unwrap_or((picks.trim(), picks.trim()));
+        loadout.push((category.trim().to_string(), min.parse::<usize>().unwrap(), max.parse::<usize>().unwrap()));
+      },
+      _ => categories.push(Category{
+        name: name.to_string(),
+        items: body.iter().map(|line| parse_item(line)).collect(),
+        min_picks: 0,
+        max_picks: 1
+      })
+    }
+  }
 
-  let mut player_dmg_dealt = player.dmg - boss.amr;
-  if player_dmg_dealt < 1 { player_dmg_dealt = 1 }
+  for (name, min, max) in loadout {
+    let category = categories.iter_mut().find(|c| c.name == name)
+      .unwrap_or_else(|| panic!("Loadout names unknown category: {}", name));
+    category.min_picks = min;
+    category.max_picks = max;
+  }
+  return Shop{player, categories};
+}
 
-  let mut boss_dmg_dealt = boss.dmg - player.amr;
-  if boss_dmg_dealt < 1 { boss_dmg_dealt = 1 }
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Outcome {
+  won: bool,
+  rounds: i32,
+  /* Hit points the winner has left. */
+  hp_left: i32
+}
 
-  while curr_player_hp > 0 && curr_boss_hp > 0 {
-    curr_boss_hp -= player_dmg_dealt;
-    if curr_boss_hp <= 0 { break }
-    curr_player_hp -= boss_dmg_dealt;
+impl Outcome {
+  /* How comfortably the player won, negative for a loss: more is better. */
+  fn margin(&self) -> i32 {
+    return if self.won { self.hp_left } else { -self.hp_left };
   }
+}
 
-  return curr_boss_hp <= 0;
+/*
+  Both sides deal the same damage every round, so the fight is decided by who
+  needs fewer hits, and the player swings first so a tie goes to them.
+*/
+fn fight(player: &Character, boss: &Character) -> Outcome {
+  let player_hit = (player.dmg - boss.amr).max(1);
+  let boss_hit = (boss.dmg - player.amr).max(1);
+  let hits_to_kill_boss = (boss.hp + player_hit - 1) / player_hit;
+  let hits_to_kill_player = (player.hp + boss_hit - 1) / boss_hit;
+
+  if hits_to_kill_boss <= hits_to_kill_player {
+    return Outcome{won: true, rounds: hits_to_kill_boss, hp_left: player.hp - (hits_to_kill_boss - 1) * boss_hit};
+  }
+  return Outcome{won: false, rounds: hits_to_kill_player, hp_left: boss.hp - hits_to_kill_player * player_hit};
 }
 
-fn calculate_min_gold_needed_to_win(shop: &Shop, boss: &Character) -> u32 {
-  let mut min_cost = u32::MAX;
-  
-  for weapon in shop.weapons.iter() {
-    for armour in shop.armour.iter() {
-      for left_ring in shop.rings.iter() {
-        for right_ring in shop.rings.iter() {
-          if left_ring.name != "None" && left_ring.name == right_ring.name { continue }
+struct Loadout<'a> {
+  items: Vec<&'a Equipment>,
+  cost: u32,
+  outcome: Outcome
+}
 
-          let cost = weapon.cost + armour.cost + left_ring.cost + right_ring.cost;
-          let dmg = weapon.dmg + armour.dmg + left_ring.dmg + right_ring.dmg;
-          let amr = weapon.amr + armour.amr + left_ring.amr + right_ring.amr;
+fn pick_from<'a>(category: &'a Category, start: usize, picked: &mut Vec<&'a Equipment>, out: &mut Vec<Vec<&'a Equipment>>) {
+  if picked.len() >= category.min_picks { out.push(picked.clone()); }
+  if picked.len() == category.max_picks { return; }
+  for index in start..category.items.len() {
+    picked.push(&category.items[index]);
+    pick_from(category, index + 1, picked, out);
+    picked.pop();
+  }
+}
 
-          let won = fight(&Character{hp: 100, dmg: dmg as i32, amr: amr as i32}, boss);
-          if won {
-            if cost < min_cost { min_cost = cost }
-          }
-        }
-      }
-    }
+fn enumerate_loadouts<'a>(shop: &'a Shop, boss: &Character) -> Vec<Loadout<'a>> {
+  let mut partial: Vec<Vec<&'a Equipment>> = vec![Vec::new()];
+  for category in shop.categories.iter() {
+    let mut choices = Vec::new();
+    pick_from(category, 0, &mut Vec::new(), &mut choices);
+    partial = partial.iter()
+      .flat_map(|items| choices.iter().map(move |choice| items.iter().chain(choice.iter()).copied().collect::<Vec<&Equipment>>()))
+      .collect();
   }
 
-  return min_cost;
+  return partial.into_iter().map(|items| {
+    let player = Character{
+      hp: shop.player.hp,
+      dmg: shop.player.dmg + items.iter().map(|item| item.dmg as i32).sum::<i32>(),
+      amr: shop.player.amr + items.iter().map(|item| item.amr as i32).sum::<i32>()
+    };
+    let cost = items.iter().map(|item| item.cost).sum();
+    Loadout{items, cost, outcome: fight(&player, boss)}
+  }).collect();
 }
 
-fn calculate_max_gold_needed_to_lose(shop: &Shop, boss: &Character) -> u32 {
-  let mut max_cost = u32::MIN;
-  
-  for weapon in shop.weapons.iter() {
-    for armour in shop.armour.iter() {
-      for left_ring in shop.rings.iter() {
-        for right_ring in shop.rings.iter() {
-          if left_ring.name != "None" && left_ring.name == right_ring.name { continue }
+fn calculate_min_gold_needed_to_win(loadouts: &[Loadout]) -> Option<u32> {
+  return loadouts.iter().filter(|loadout| loadout.outcome.won).map(|loadout| loadout.cost).min();
+}
 
-          let cost = weapon.cost + armour.cost + left_ring.cost + right_ring.cost;
-          let dmg = weapon.dmg + armour.dmg + left_ring.dmg + right_ring.dmg;
-          let amr = weapon.amr + armour.amr + left_ring.amr + right_ring.amr;
+fn calculate_max_gold_needed_to_lose(loadouts: &[Loadout]) -> Option<u32> {
+  return loadouts.iter().filter(|loadout| !loadout.outcome.won).map(|loadout| loadout.cost).max();
+}
 
-          let won = fight(&Character{hp: 100, dmg: dmg as i32, amr: amr as i32}, boss);
-          if !won {
-            if cost > max_cost { max_cost = cost }
-          }
-        }
-      }
-    }
+/* Loadouts no other loadout beats on both cost and margin, cheapest first. */
+fn pareto_front<'a, 'b>(loadouts: &'b [Loadout<'a>]) -> Vec<&'b Loadout<'a>> {
+  let mut sorted: Vec<&Loadout> = loadouts.iter().collect();
+  sorted.sort_by(|a, b| a.cost.cmp(&b.cost).then(b.outcome.margin().cmp(&a.outcome.margin())));
+  let mut front: Vec<&Loadout> = Vec::new();
+  for loadout in sorted {
+    if front.last().is_none_or(|best| loadout.outcome.margin() > best.outcome.margin()) { front.push(loadout); }
   }
+  return front;
+}
 
-  return max_cost;
+fn print_answer(part: u32, answer: Option<u32>) {
+  match answer {
+    Some(cost) => println!("Part {}: {}", part, cost),
+    None => println!("Part {}: no loadout does that", part)
+  }
 }
 
 fn main() {
+  let args = Args::parse();
   let contents = fs::read_to_string("./data/q21.txt")
     .expect("Should have been able to read file");
-
-  let lines = contents.split("\n").collect::<Vec<&str>>();
-  let boss_hp = lines[0].split(": ").collect::<Vec<&str>>()[1].parse::<i32>().unwrap();
-  let boss_dmg = lines[1].split(": ").collect::<Vec<&str>>()[1].parse::<i32>().unwrap();
-  let boss_amr = lines[2].split(": ").collect::<Vec<&str>>()[1].parse::<i32>().unwrap();
-  let boss = Character {hp: boss_hp, dmg: boss_dmg, amr: boss_amr};
-
-  let weapons: Vec<Equipment> = vec![
-    Equipment {name: "Dagger".to_string(), cost: 8, dmg: 4, amr: 0},
-    Equipment {name: "Shortsword".to_string(), cost: 10, dmg: 5, amr: 0},
-    Equipment {name: "Warhammer".to_string(), cost: 25, dmg: 6, amr: 0},
-    Equipment {name: "Longsword".to_string(), cost: 40, dmg: 7, amr: 0},
-    Equipment {name: "Greataxe".to_string(), cost: 74, dmg: 8, amr: 0}
-  ];
-
-  let armour: Vec<Equipment> = vec![
-    Equipment {name: "None".to_string(), cost: 0, dmg: 0, amr: 0},
-    Equipment {name: "Leather".to_string(), cost: 13, dmg: 0, amr: 1},
-    Equipment {name: "Chainmail".to_string(), cost: 31, dmg: 0, amr: 2},
-    Equipment {name: "Splintmail".to_string(), cost: 53, dmg: 0, amr: 3},
-    Equipment {name: "Bandedmail".to_string(), cost: 75, dmg: 0, amr: 4},
-    Equipment {name: "Platemail".to_string(), cost: 102, dmg: 0, amr: 5}
-  ];
-
-  let rings: Vec<Equipment> = vec![
-    Equipment {name: "None".to_string(), cost: 0, dmg: 0, amr: 0},
-    Equipment {name: "Damage +1".to_string(), cost: 25, dmg: 1, amr: 0},
-    Equipment {name: "Damage +2".to_string(), cost: 50, dmg: 2, amr: 0},
-    Equipment {name: "Damage +3".to_string(), cost: 100, dmg: 3, amr: 0},
-    Equipment {name: "Defence +1".to_string(), cost: 20, dmg: 0, amr: 1},
-    Equipment {name: "Defence +2".to_string(), cost: 40, dmg: 0, amr: 2},
-    Equipment {name: "Defence +3".to_string(), cost: 80, dmg: 0, amr: 3}
-  ];
-
-  let shop = Shop{weapons: weapons, armour: armour, rings: rings};
-
-  println!("Part 1: {}", calculate_min_gold_needed_to_win(&shop, &boss));
-  println!("Part 2: {}", calculate_max_gold_needed_to_lose(&shop, &boss));
-}
\ No newline at end of file
+  let boss = parse_character(&contents.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<&str>>());
+
+  let shop_contents = match args.value("--shop") {
+    Some(path) => fs::read_to_string(path).expect("Should have been able to read file"),
+    None => fs::read_to_string("./data/q21_shop.txt").unwrap_or(DEFAULT_SHOP.to_string())
+  };
+  let shop = parse_shop(&shop_contents);
+  let loadouts = enumerate_loadouts(&shop, &boss);
+
+  print_answer(1, calculate_min_gold_needed_to_win(&loadouts));
+  print_answer(2, calculate_max_gold_needed_to_lose(&loadouts));
+
+  if args.flag("--pareto") {
+    for loadout in pareto_front(&loadouts) {
+      let outcome = loadout.outcome;
+      let result = if outcome.won { "win with" } else { "lose, boss keeps" };
+      let items = loadout.items.iter().map(|item| item.name.as_str()).collect::<Vec<&str>>().join(", ");
+      println!("  {:>4} gold: {} {} HP after {} rounds [{}]", loadout.cost, result, outcome.hp_left, outcome.rounds, items);
+    }
+  }
+}
@@ -1,6 +1,62 @@
-use std::{fs, collections::HashMap};
+use std::{fs, cmp::Reverse, collections::{BinaryHeap, HashMap, HashSet}};
+use aoc_common::cli::Args;
+use aoc_common::trace::Tracer;
+use aoc_common::{trace_leaf, trace_memo_hit, trace_node, trace_prune};
 
-const MIN_MANA_NEEDED: i32 = 53;
+const DEFAULT_SPELLBOOK: &str = "Magic Missile: cost 53, damage 4
+Drain: cost 73, damage 2, heal 2
+Shield: cost 113, turns 6, armor 7
+Poison: cost 173, turns 6, tick_damage 3
+Recharge: cost 229, turns 5, tick_mana 101
+";
+
+/* Effect timers are packed four bits apiece into a `u64`. */
+const TIMER_BITS: u32 = 4;
+const MAX_SPELLS: usize = (u64::BITS / TIMER_BITS) as usize;
+const MAX_TURNS: u32 = (1 << TIMER_BITS) - 1;
+
+/* Hit points or mana that a repeatable loop can raise as high as needed. */
+const UNBOUNDED: i32 = i32::MAX / 2;
+
+#[derive(Debug, Clone, Default)]
+struct Spell {
+  name: String,
+  cost: i32,
+  damage: i32,
+  heal: i32,
+  turns: u32,
+  armor: i32,
+  tick_damage: i32,
+  tick_mana: i32
+}
+
+fn parse_spellbook(contents: &str) -> Vec<Spell> {
+  let mut spells = Vec::new();
+  for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+    let (name, fields) = line.split_once(": ").unwrap_or_else(|| panic!("Bad spell line: {}", line));
+    let mut spell = Spell{name: name.trim().to_string(), ..Spell::default()};
+    for field in fields.split(", ") {
+      let (key, value) = field.trim().split_once(' ').unwrap_or_else(|| panic!("Bad spell field: {}", field));
+      let value = value.trim().parse::<i32>().unwrap();
+      if value < 0 { panic!("{} can't have a negative {}", spell.name, key); }
+      match key {
+        "cost" => spell.cost = value,
+        "damage" => spell.damage = value,
+        "heal" => spell.heal = value,
+        "turns" => spell.turns = value as u32,
+        "armor" => spell.armor = value,
+        "tick_damage" => spell.tick_damage = value,
+        "tick_mana" => spell.tick_mana = value,
+        _ => panic!("Unknown spell field: {}", key)
+      }
+    }
+    if spell.cost < 1 { panic!("{} has to cost at least 1 mana", spell.name); }
+    if spell.turns > MAX_TURNS { panic!("{} lasts longer than {} turns", spell.name, MAX_TURNS); }
+    spells.push(spell);
+  }
+  if spells.len() > MAX_SPELLS { panic!("At most {} spells fit in a battle state", MAX_SPELLS); }
+  return spells;
+}
 
 #[derive(Debug, Clone)]
 struct Boss {
@@ -8,140 +64,270 @@ struct Boss {
   dmg: i32
 }
 
-#[derive(Debug, Clone)]
-struct Player {
-  hp: i32,
+/* Everything about a fight at the start of one of the player's turns. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct State {
+  player_hp: i32,
   mana: i32,
-  amr: i32
+  boss_hp: i32,
+  timers: u64
+}
+
+impl State {
+  /* Carries unbounded hit points or mana over from `before`, whatever a turn took off them. */
+  fn keep_unbounded(mut self, before: &State) -> State {
+    if before.player_hp >= UNBOUNDED { self.player_hp = UNBOUNDED; }
+    if before.mana >= UNBOUNDED { self.mana = UNBOUNDED; }
+    return self;
+  }
+
+  fn timer(&self, spell: usize) -> u32 {
+    return (self.timers >> (spell as u32 * TIMER_BITS)) as u32 & MAX_TURNS;
+  }
+
+  fn set_timer(&mut self, spell: usize, turns: u32) {
+    let shift = spell as u32 * TIMER_BITS;
+    self.timers = (self.timers & !((MAX_TURNS as u64) << shift)) | ((turns as u64) << shift);
+  }
+}
+
+enum Turn {
+  Continue(State),
+  Won,
+  Lost
 }
 
-fn simulate_battle(
-  player: &Player,
-  boss: &Boss,
-  active_effects: &HashMap<String, u32>,
-  mana_spent: u32,
-  is_player_turn: bool,
+struct Battle<'a> {
+  spells: &'a [Spell],
+  boss: Boss,
   hard_mode: bool
-) -> (bool, u32) {
-  let mut next_player = player.clone();
-  let mut next_boss = boss.clone();
-  let mut next_active_effects: HashMap<String, u32> = HashMap::new();
-  let effects: Vec<&str> = vec!["magic missile", "drain", "shield", "poison", "recharge"];
-  let costs: HashMap<&str, i32> = HashMap::from_iter([
-    ("magic missile", 53),
-    ("drain", 73),
-    ("shield", 113),
-    ("poison", 173),
-    ("recharge", 229)
-  ]);
-
-  if is_player_turn && hard_mode { next_player.hp -= 1 }
-
-  for (effect, time) in active_effects {
-    let next_time = time - 1;
-    match effect.as_str() {
-      "shield" => {
-        if next_time == 0 { next_player.amr = 0 }
-      },
-      "poison" => {
-        next_boss.hp -= 3;
-      },
-      "recharge" => {
-        next_player.mana += 101;
-      },
-      _ => println!("Was unable to match effect: {}", effect)
-    }
+}
 
-    if next_time != 0 { next_active_effects.insert(effect.clone(), next_time); }
+impl<'a> Battle<'a> {
+  /* Applies every active effect and returns the armour they give this turn. */
+  fn tick(&self, state: &mut State, log: &mut Option<&mut Vec<String>>) -> i32 {
+    let mut armor = 0;
+    for (index, spell) in self.spells.iter().enumerate() {
+      let timer = state.timer(index);
+      if timer == 0 { continue; }
+      state.boss_hp -= spell.tick_damage;
+      state.mana += spell.tick_mana;
+      state.set_timer(index, timer - 1);
+      if timer > 1 { armor += spell.armor; }
+      if let Some(log) = log.as_mut() {
+        log.push(format!("{}'s timer is now {}.{}", spell.name, timer - 1,
+          if timer == 1 { format!(" {} wears off.", spell.name) } else { String::new() }));
+      }
+    }
+    return armor;
   }
 
-  if next_boss.hp <= 0 { return (true, mana_spent) }
+  fn describe(&self, state: &State, armor: i32, log: &mut Option<&mut Vec<String>>) {
+    if let Some(log) = log.as_mut() {
+      log.push(format!("- Player has {} hit points, {} armor, {} mana", state.player_hp, armor, state.mana));
+      log.push(format!("- Boss has {} hit points", state.boss_hp));
+    }
+  }
 
-  if next_player.hp <= 0 { return (false, mana_spent) }
+  fn active_armor(&self, state: &State) -> i32 {
+    return self.spells.iter().enumerate().filter(|(i, _)| state.timer(*i) > 0).map(|(_, s)| s.armor).sum();
+  }
 
-  if next_player.mana < MIN_MANA_NEEDED { return (false, mana_spent) }
+  fn start_turn(&self, state: &State, log: &mut Option<&mut Vec<String>>) -> Turn {
+    let mut state = *state;
+    if let Some(log) = log.as_mut() { log.push("-- Player turn --".to_string()); }
+    self.describe(&state, self.active_armor(&state), log);
+    if self.hard_mode {
+      state.player_hp -= 1;
+      if state.player_hp <= 0 { return Turn::Lost; }
+    }
+    self.tick(&mut state, log);
+    if state.boss_hp <= 0 { return Turn::Won; }
+    return Turn::Continue(state);
+  }
 
-  if is_player_turn {
-    let mut min_manas: Vec<u32> = Vec::new();
+  fn cast_and_defend(&self, state: &State, spell: usize, log: &mut Option<&mut Vec<String>>) -> Turn {
+    let mut state = *state;
+    let cast = &self.spells[spell];
+    state.mana -= cast.cost;
+    state.boss_hp -= cast.damage;
+    state.player_hp += cast.heal;
+    if cast.turns > 0 { state.set_timer(spell, cast.turns); }
+    if let Some(log) = log.as_mut() { log.push(format!("Player casts {}.", cast.name)); }
+    if state.boss_hp <= 0 { return Turn::Won; }
 
-    for effect in effects.iter() {
-      if next_active_effects.contains_key(&effect.to_string()) { continue }
+    if let Some(log) = log.as_mut() { log.push(String::new()); log.push("-- Boss turn --".to_string()); }
+    self.describe(&state, self.active_armor(&state), log);
+    let armor = self.tick(&mut state, log);
+    if state.boss_hp <= 0 { return Turn::Won; }
+    let hit = (self.boss.dmg - armor).max(1);
+    state.player_hp -= hit;
+    if let Some(log) = log.as_mut() { log.push(format!("Boss attacks for {} damage.", hit)); log.push(String::new()); }
+    if state.player_hp <= 0 { return Turn::Lost; }
+    return Turn::Continue(state);
+  }
 
-      let mut curr_player = next_player.clone();
-      let mut curr_boss = next_boss.clone();
-      let mut curr_active_effects = next_active_effects.clone();
+  fn can_cast(&self, state: &State, spell: usize) -> bool {
+    return state.timer(spell) == 0 && state.mana >= self.spells[spell].cost;
+  }
 
-      let cost = costs.get(effect).unwrap();
-      if curr_player.mana < *cost { continue }
-      curr_player.mana -= cost;
-      let next_mana_spent = mana_spent + (*cost as u32);
+  /*
+    Whether any sequence of spells wins at all. More hit points or mana never
+    hurt, so when a fight comes back to the same boss hit points and timers
+    with no less of either, the loop can be run as often as needed and
+    whatever grew is taken as unbounded. As in a Karp-Miller tree, every path
+    then ends, which Dijkstra alone doesn't manage when healing or recharging
+    outpaces the boss.
+  */
+  fn can_win(&self, state: State, path: &mut Vec<State>, seen: &mut HashSet<State>) -> bool {
+    if !seen.insert(state) { return false; }
+    let state_after_effects = match self.start_turn(&state, &mut None) {
+      Turn::Won => return true,
+      Turn::Lost => return false,
+      Turn::Continue(next) => next.keep_unbounded(&state)
+    };
 
-      match *effect {
-        "magic missile" => curr_boss.hp -= 4, 
-        "drain" => {
-          curr_boss.hp -= 2;
-          curr_player.hp += 2;
-        }, 
-        "shield" => {
-          curr_player.amr = 7;
-          curr_active_effects.insert(effect.to_string(), 6);
-        }, 
-        "poison" => {
-          curr_active_effects.insert(effect.to_string(), 6);
-        }
-        "recharge" => {
-          curr_active_effects.insert(effect.to_string(), 5);
-        }
-        _ => println!("Was unable to match effect {}", effect),
+    path.push(state);
+    for spell in 0..self.spells.len() {
+      if !self.can_cast(&state_after_effects, spell) { continue; }
+      let mut next = match self.cast_and_defend(&state_after_effects, spell, &mut None) {
+        Turn::Won => return true,
+        Turn::Lost => continue,
+        Turn::Continue(next) => next.keep_unbounded(&state_after_effects)
+      };
+      for earlier in path.iter() {
+        if earlier.boss_hp != next.boss_hp || earlier.timers != next.timers { continue; }
+        if earlier.player_hp > next.player_hp || earlier.mana > next.mana { continue; }
+        if next.player_hp > earlier.player_hp { next.player_hp = UNBOUNDED; }
+        if next.mana > earlier.mana { next.mana = UNBOUNDED; }
       }
-
-      let (success, final_mana_spent)
-        = simulate_battle(&curr_player, &curr_boss, &curr_active_effects, 
-            next_mana_spent, !is_player_turn, hard_mode);
-      if success { min_manas.push(final_mana_spent) }
+      if self.can_win(next, path, seen) { return true; }
     }
+    path.pop();
+    return false;
+  }
+
+  /*
+    Dijkstra over battle states ordered by mana spent. A won fight is queued
+    as a state with the boss at zero hit points, so the first one popped is the
+    cheapest win. Every spell costs something, so once `can_win` has vouched
+    for a win there are only finitely many cheaper states to go through.
+  */
+  fn cheapest_win(&self, start: State, tracer: &Tracer) -> Option<(u32, Vec<usize>)> {
+    if !self.can_win(start, &mut Vec::new(), &mut HashSet::new()) { return None; }
+    let mut spent: HashMap<State, u32> = HashMap::new();
+    let mut parents: HashMap<State, (State, usize)> = HashMap::new();
+    let mut queue: BinaryHeap<Reverse<(u32, usize, State)>> = BinaryHeap::new();
+    spent.insert(start, 0);
+    queue.push(Reverse((0, 0, start)));
 
-    if !min_manas.is_empty() { return (true, *min_manas.iter().min().unwrap()) }
-  } else {
-    let mut boss_dmg = next_boss.dmg - next_player.amr;
-    if boss_dmg < 1 { boss_dmg = 1 }
+    while let Some(Reverse((cost, depth, state))) = queue.pop() {
+      if spent.get(&state).is_some_and(|best| *best < cost) { continue; }
+      trace_node!(tracer, depth, "player hp={} mana={} boss hp={} timers={:x} spent={}",
+        state.player_hp, state.mana, state.boss_hp, state.timers, cost);
 
-    next_player.hp -= boss_dmg;
+      let turn = if state.boss_hp <= 0 { Turn::Won } else { self.start_turn(&state, &mut None) };
+      let state_after_effects = match turn {
+        Turn::Won => {
+          trace_leaf!(tracer, depth, "boss dies, spent={}", cost);
+          return Some((cost, self.spell_sequence(&parents, state)));
+        },
+        Turn::Lost => { trace_leaf!(tracer, depth, "player dies"); continue; },
+        Turn::Continue(next) => next
+      };
 
-    let (success, final_mana_spent) 
-      = simulate_battle(&next_player, &next_boss, &next_active_effects, 
-          mana_spent, !is_player_turn, hard_mode);
-    
-    if success { return (success, final_mana_spent) }
+      for spell in 0..self.spells.len() {
+        if !self.can_cast(&state_after_effects, spell) {
+          trace_prune!(tracer, depth, "cannot cast {}", self.spells[spell].name);
+          continue;
+        }
+        let next = match self.cast_and_defend(&state_after_effects, spell, &mut None) {
+          Turn::Continue(next) => next,
+          Turn::Won => State{boss_hp: 0, ..state_after_effects},
+          Turn::Lost => { trace_leaf!(tracer, depth + 1, "player dies after {}", self.spells[spell].name); continue; }
+        };
+        let next_cost = cost + self.spells[spell].cost as u32;
+        if spent.get(&next).is_some_and(|best| *best <= next_cost) {
+          trace_memo_hit!(tracer, depth + 1, "already reached for {}", spent[&next]);
+          continue;
+        }
+        spent.insert(next, next_cost);
+        parents.insert(next, (state, spell));
+        queue.push(Reverse((next_cost, depth + 1, next)));
+      }
+    }
+    return None;
   }
 
-  return (false, mana_spent);
-}
+  fn spell_sequence(&self, parents: &HashMap<State, (State, usize)>, mut state: State) -> Vec<usize> {
+    let mut spells = Vec::new();
+    while let Some((parent, spell)) = parents.get(&state) {
+      spells.push(*spell);
+      state = *parent;
+    }
+    spells.reverse();
+    return spells;
+  }
 
-fn get_min_mana_to_win(player: &Player, boss: &Boss) -> u32 {
-  let (_, min_mana) 
-    = simulate_battle(player, boss, &HashMap::new(), 
-      0, true, false);
-  return min_mana;
+  fn replay(&self, start: State, spells: &[usize]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut state = start;
+    let mut turn = Turn::Continue(start);
+    for spell in spells.iter() {
+      let mut log = Some(&mut lines);
+      turn = match self.start_turn(&state, &mut log) {
+        Turn::Continue(after_effects) => self.cast_and_defend(&after_effects, *spell, &mut log),
+        ended => ended
+      };
+      match turn {
+        Turn::Continue(next) => state = next,
+        _ => break
+      }
+    }
+    if let Turn::Continue(_) = turn { turn = self.start_turn(&state, &mut Some(&mut lines)); }
+    lines.push(match turn {
+      Turn::Won => "This kills the boss, and the player wins.".to_string(),
+      _ => "The player does not win.".to_string()
+    });
+    return lines;
+  }
 }
 
-fn get_min_mana_to_win_hard(player: &Player, boss: &Boss) -> u32 {
-  let (_, min_mana) 
-    = simulate_battle(player, boss, &HashMap::new(), 
-      0, true, true);
-  return min_mana;
+fn play(label: &str, part: u32, battle: &Battle, start: State, tracer: &Tracer, replay: bool) {
+  match battle.cheapest_win(start, tracer) {
+    Some((mana, spells)) => {
+      println!("Part {}: {}", part, mana);
+      if replay {
+        let names = spells.iter().map(|s| battle.spells[*s].name.as_str()).collect::<Vec<&str>>();
+        println!("  {}", names.join(" -> "));
+        for line in battle.replay(start, &spells) { println!("    {}", line); }
+      }
+    },
+    None => println!("Part {}: no winning sequence", part)
+  }
+  tracer.summary(label);
 }
 
-
 fn main() {
+  let args = Args::parse();
+  let tracer = Tracer::from_args(&args);
   let contents = fs::read_to_string("./data/q22.txt")
     .expect("Should have been able to read file");
   let lines = contents.split("\n").collect::<Vec<&str>>();
   let boss_hp = lines[0].split(": ").collect::<Vec<&str>>()[1].parse::<i32>().unwrap();
   let boss_dmg= lines[1].split(": ").collect::<Vec<&str>>()[1].parse::<i32>().unwrap();
   let boss = Boss{hp: boss_hp, dmg: boss_dmg};
-  let player = Player{hp: 50, mana: 500, amr: 0};
 
-  println!("Part 1: {}", get_min_mana_to_win(&player, &boss));
-  println!("Part 2: {}", get_min_mana_to_win_hard(&player, &boss));
-}
\ No newline at end of file
+  let spellbook = match args.value("--spellbook") {
+    Some(path) => fs::read_to_string(path).expect("Should have been able to read file"),
+    None => DEFAULT_SPELLBOOK.to_string()
+  };
+  let spells = parse_spellbook(&spellbook);
+  let player_hp = args.value("--player-hp").map_or(50, |hp| hp.parse::<i32>().unwrap());
+  let player_mana = args.value("--player-mana").map_or(500, |mana| mana.parse::<i32>().unwrap());
+  let start = State{player_hp, mana: player_mana, boss_hp: boss.hp, timers: 0};
+  let replay = args.flag("--replay");
+
+  play("normal mode", 1, &Battle{spells: &spells, boss: boss.clone(), hard_mode: false}, start, &tracer, replay);
+  play("hard mode", 2, &Battle{spells: &spells, boss, hard_mode: true}, start, &tracer, replay);
+}
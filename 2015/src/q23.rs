@@ -1,108 +1,512 @@
-use std::{fs, collections::HashMap};
+use std::{fmt, fs, io::{self, BufRead, Write}};
+use aoc_common::cli::Args;
+use aoc_common::vm::{self, Exit, Flow, Hook, Machine, Reg, State, Word};
+
+const REGISTERS: &[&str; 2] = &["a", "b"];
+const HISTOGRAM_WIDTH: u64 = 40;
 
 #[derive(Debug, Clone)]
 enum Instruction {
-  Hlf(String),
-  Tpl(String),
-  Inc(String),
-  Jmp(i32),
-  Jie(String, i32),
-  Jio(String, i32)
-}
-
-fn run(instructions: &Vec<Instruction>, registers: &mut HashMap<String, u32>) {
-  let mut index: usize = 0;
-
-  while index < instructions.len() {
-    match &instructions[index] {
-      Instruction::Hlf(reg) => {
-        *registers.get_mut(reg).unwrap() /= 2;
-        index += 1;
-      }
+  Hlf(Reg),
+  Tpl(Reg),
+  Inc(Reg),
+  Jmp(Word),
+  Jie(Reg, Word),
+  Jio(Reg, Word)
+}
 
-      Instruction::Tpl(reg) => {
-        *registers.get_mut(reg).unwrap() *= 3;
-        index += 1;
-      }
+impl Instruction {
+  fn offset(&self) -> Option<Word> {
+    return match self {
+      Instruction::Jmp(offset) | Instruction::Jie(_, offset) | Instruction::Jio(_, offset) => Some(*offset),
+      _ => None
+    };
+  }
+}
 
-      Instruction::Inc(reg) => {
-        *registers.get_mut(reg).unwrap() += 1;
-        index += 1;
-      }
+impl vm::Instruction for Instruction {
+  fn execute(&self, state: &mut State) -> Flow {
+    match self {
+      Instruction::Hlf(reg) => state.set(*reg, state.get(*reg) / 2),
+      Instruction::Tpl(reg) => state.set(*reg, state.get(*reg) * 3),
+      Instruction::Inc(reg) => state.set(*reg, state.get(*reg) + 1),
+      Instruction::Jmp(offset) => return Flow::Jump(*offset),
+      Instruction::Jie(reg, offset) => if state.get(*reg) % 2 == 0 { return Flow::Jump(*offset); },
+      Instruction::Jio(reg, offset) => if state.get(*reg) == 1 { return Flow::Jump(*offset); }
+    }
+    return Flow::Next;
+  }
+}
 
-      Instruction::Jmp(offset) => {
-        index = (index as i32 + offset) as usize;
-      }
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = |reg: &Reg| REGISTERS[reg.0];
+    match self {
+      Instruction::Hlf(reg) => write!(f, "hlf {}", name(reg)),
+      Instruction::Tpl(reg) => write!(f, "tpl {}", name(reg)),
+      Instruction::Inc(reg) => write!(f, "inc {}", name(reg)),
+      Instruction::Jmp(offset) => write!(f, "jmp {:+}", offset),
+      Instruction::Jie(reg, offset) => write!(f, "jie {}, {:+}", name(reg), offset),
+      Instruction::Jio(reg, offset) => write!(f, "jio {}, {:+}", name(reg), offset)
+    }
+  }
+}
 
-      Instruction::Jie(reg, offset) => {
-        if *registers.get(reg).unwrap() % 2 == 0 {
-          index = (index as i32 + offset) as usize;
-        } else {
-          index += 1;
-        }
-      }
+#[derive(Debug)]
+struct AsmError {
+  line: usize,
+  message: String
+}
 
-      Instruction::Jio(reg, offset) => {
-        if *registers.get(reg).unwrap() == 1 {
-          index = (index as i32 + offset) as usize;
-        } else {
-          index += 1;
+impl fmt::Display for AsmError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+fn parse_register(name: &str) -> Option<Reg> {
+  return REGISTERS.iter().position(|reg| *reg == name.trim()).map(Reg);
+}
+
+fn is_label(name: &str) -> bool {
+  return name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+}
+
+/*
+  Besides the puzzle's own syntax the assembler takes `;` comments, `name:`
+  labels that jumps may name instead of an offset, and the `12:` addresses the
+  disassembler prints, so its listings assemble back into the same program.
+*/
+fn assemble(source: &str) -> Result<Vec<Instruction>, AsmError> {
+  let mut labels: Vec<(String, usize)> = Vec::new();
+  let mut statements: Vec<(usize, &str)> = Vec::new();
+
+  for (number, line) in source.lines().enumerate() {
+    let mut text = line.split(';').next().unwrap().trim();
+    while let Some((head, rest)) = text.split_once(':') {
+      let head = head.trim();
+      if head.chars().all(|c| c.is_ascii_digit()) && !head.is_empty() {
+        text = rest.trim();
+      } else if is_label(head) {
+        if labels.iter().any(|(label, _)| label == head) {
+          return Err(AsmError{line: number + 1, message: format!("label {} is defined twice", head)});
         }
+        labels.push((head.to_string(), statements.len()));
+        text = rest.trim();
+      } else {
+        break;
       }
-    } 
+    }
+    if !text.is_empty() { statements.push((number + 1, text)); }
+  }
+
+  let mut program = Vec::new();
+  for (address, (line, text)) in statements.iter().enumerate() {
+    let error = |message: String| AsmError{line: *line, message};
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let operands = operands.split(',').map(|operand| operand.trim()).filter(|operand| !operand.is_empty())
+      .collect::<Vec<&str>>();
+
+    let register = |index: usize| operands.get(index)
+      .ok_or_else(|| error(format!("{} is missing a register", mnemonic)))
+      .and_then(|name| parse_register(name).ok_or_else(|| error(format!("unknown register {}", name))));
+    let offset = |index: usize| operands.get(index)
+      .ok_or_else(|| error(format!("{} is missing a jump target", mnemonic)))
+      .and_then(|target| match target.parse::<Word>() {
+        Ok(offset) => Ok(offset),
+        Err(_) => labels.iter().find(|(label, _)| label == target)
+          .map(|(_, target)| *target as Word - address as Word)
+          .ok_or_else(|| error(format!("unknown label {}", target)))
+      });
+
+    let expected = if mnemonic == "jie" || mnemonic == "jio" { 2 } else { 1 };
+    if operands.len() > expected {
+      return Err(error(format!("{} takes {} operand(s)", mnemonic, expected)));
+    }
+    program.push(match mnemonic {
+      "hlf" => Instruction::Hlf(register(0)?),
+      "tpl" => Instruction::Tpl(register(0)?),
+      "inc" => Instruction::Inc(register(0)?),
+      "jmp" => Instruction::Jmp(offset(0)?),
+      "jie" => Instruction::Jie(register(0)?, offset(1)?),
+      "jio" => Instruction::Jio(register(0)?, offset(1)?),
+      _ => return Err(error(format!("Was unable to match line: {}", text)))
+    });
   }
+  return Ok(program);
 }
 
-fn run_and_get_value_of_reg(instructions: &Vec<Instruction>, register: String) -> u32 {
-  let mut registers = HashMap::from_iter([
-    ("a".to_string(), 0),
-    ("b".to_string(), 0)
-  ]);
-  run(instructions, &mut registers);
-  return registers[&register];
+fn jump_target(program: &[Instruction], address: usize) -> Option<Word> {
+  return program[address].offset().map(|offset| address as Word + offset);
 }
 
-fn set_reg_run_and_get_value_of_reg(instructions: &Vec<Instruction>, register: String) -> u32 {
-  let mut registers = HashMap::from_iter([
-    ("a".to_string(), 1),
-    ("b".to_string(), 0)
-  ]);
-  run(instructions, &mut registers);
-  return registers[&register];
+fn disassemble_line(program: &[Instruction], address: usize) -> String {
+  let text = format!("{:>4}: {}", address, program[address]);
+  return match jump_target(program, address) {
+    Some(target) if target < 0 || target as usize >= program.len() => format!("{:<20}; -> {} (halts)", text, target),
+    Some(target) => format!("{:<20}; -> {}", text, target),
+    None => text
+  };
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q23.txt")
-    .expect("Should have been able to read file");
-  let mut instructions: Vec<Instruction> = Vec::new();
-
-  for line in contents.split("\n") {
-    let parts = line.split(" ").collect::<Vec<&str>>();
-    match parts[0] {
-      "hlf" => instructions.push(Instruction::Hlf(parts[1].to_string())),
-      
-      "tpl" => instructions.push(Instruction::Tpl(parts[1].to_string())),
-      
-      "inc" => instructions.push(Instruction::Inc(parts[1].to_string())),
-      
-      "jmp" => instructions.push(Instruction::Jmp(parts[1].parse::<i32>().unwrap())),
-      
-      "jie" => {
-        let register = parts[1].chars().nth(0).unwrap().to_string();
-        let offset = parts[2].parse::<i32>().unwrap();
-        instructions.push(Instruction::Jie(register, offset));
+fn disassemble(program: &[Instruction]) -> String {
+  return (0..program.len()).map(|address| disassemble_line(program, address) + "\n").collect();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge
+}
+
+impl CompareOp {
+  const ALL: [(&'static str, CompareOp); 6] = [
+    ("==", CompareOp::Eq), ("!=", CompareOp::Ne), ("<=", CompareOp::Le),
+    (">=", CompareOp::Ge), ("<", CompareOp::Lt), (">", CompareOp::Gt)
+  ];
+
+  fn holds(&self, left: Word, right: Word) -> bool {
+    return match self {
+      CompareOp::Eq => left == right,
+      CompareOp::Ne => left != right,
+      CompareOp::Lt => left < right,
+      CompareOp::Le => left <= right,
+      CompareOp::Gt => left > right,
+      CompareOp::Ge => left >= right
+    };
+  }
+
+  fn symbol(&self) -> &'static str {
+    return CompareOp::ALL.iter().find(|(_, op)| op == self).unwrap().0;
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Breakpoint {
+  At(usize),
+  When(Reg, CompareOp, Word)
+}
+
+impl Breakpoint {
+  /* Either an address or a register condition such as `a==1` or `b>100`. */
+  fn parse(spec: &str) -> Result<Breakpoint, String> {
+    let spec = spec.replace(' ', "");
+    if let Ok(address) = spec.parse::<usize>() { return Ok(Breakpoint::At(address)); }
+    let (symbol, op) = CompareOp::ALL.iter().find(|(symbol, _)| spec.contains(symbol))
+      .ok_or_else(|| format!("expected an address or a condition like a==1 but got {}", spec))?;
+    let (register, value) = spec.split_once(symbol).unwrap();
+    let register = parse_register(register).ok_or_else(|| format!("unknown register {}", register))?;
+    let value = value.parse::<Word>().map_err(|_| format!("bad value {}", value))?;
+    return Ok(Breakpoint::When(register, *op, value));
+  }
+
+  /*
+    A condition fires when it becomes true, judged against the registers at the
+    previous check, rather than on every step for as long as it stays true.
+  */
+  fn hit(&self, state: &State, before: Option<&[Word]>) -> bool {
+    return match self {
+      Breakpoint::At(address) => state.pc == *address,
+      Breakpoint::When(reg, op, value) => op.holds(state.get(*reg), *value)
+        && !before.is_some_and(|registers| op.holds(registers[reg.0], *value))
+    };
+  }
+}
+
+impl fmt::Display for Breakpoint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Breakpoint::At(address) => write!(f, "pc == {}", address),
+      Breakpoint::When(reg, op, value) => write!(f, "{} {} {}", REGISTERS[reg.0], op.symbol(), value)
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cycle {
+  /* The number of instructions executed when the repeat was seen. */
+  seen_at: u64,
+  period: u64
+}
+
+/*
+  The machine is deterministic, so it never halts once a (pc, registers) state
+  comes round again. Brent's method spots that with one saved state: it is
+  replaced at every power of two steps, and a loop of length p is caught once
+  the saved state lies inside it and the power has grown past p.
+*/
+struct LoopDetector {
+  saved_pc: usize,
+  saved_registers: Vec<Word>,
+  saved_at: u64,
+  power: u64
+}
+
+impl LoopDetector {
+  fn new(state: &State) -> LoopDetector {
+    return LoopDetector{saved_pc: state.pc, saved_registers: state.registers.clone(), saved_at: state.executed, power: 1};
+  }
+
+  fn observe(&mut self, state: &State) -> Option<Cycle> {
+    let elapsed = state.executed - self.saved_at;
+    if elapsed > 0 && state.pc == self.saved_pc && state.registers == self.saved_registers {
+      let cycle = Cycle{seen_at: state.executed, period: elapsed};
+      *self = LoopDetector::new(state);
+      return Some(cycle);
+    }
+    if elapsed == self.power {
+      self.saved_pc = state.pc;
+      self.saved_registers.clone_from(&state.registers);
+      self.saved_at = state.executed;
+      self.power *= 2;
+    }
+    return None;
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stop {
+  Stepped,
+  Breakpoint(usize),
+  Loop(Cycle)
+}
+
+/*
+  Runs alongside the machine as a hook: counts how often each instruction
+  runs, reports watched registers as they change, and refuses the next
+  instruction when a breakpoint or the loop detector fires or the requested
+  number of single steps is used up.
+*/
+struct Debugger {
+  breakpoints: Vec<Breakpoint>,
+  watches: Vec<Reg>,
+  counts: Vec<u64>,
+  loops: LoopDetector,
+  previous: Vec<Word>,
+  checked: Option<Vec<Word>>,
+  steps_left: Option<u64>,
+  resume_at: Option<usize>,
+  stop: Option<Stop>
+}
+
+impl Debugger {
+  fn new(program: &[Instruction], state: &State) -> Debugger {
+    return Debugger{
+      breakpoints: Vec::new(),
+      watches: Vec::new(),
+      counts: vec![0; program.len()],
+      loops: LoopDetector::new(state),
+      previous: state.registers.clone(),
+      checked: None,
+      steps_left: None,
+      resume_at: None,
+      stop: None
+    };
+  }
+
+  /* Lets the instruction the machine stopped at run, even if it is a breakpoint. */
+  fn resume(&mut self, state: &State, steps: Option<u64>) {
+    self.resume_at = Some(state.pc);
+    self.steps_left = steps;
+    self.stop = None;
+  }
+}
+
+impl Hook<Instruction> for Debugger {
+  fn before_execute(&mut self, state: &State, _instr: &Instruction) -> bool {
+    if let Some(cycle) = self.loops.observe(state) {
+      self.stop = Some(Stop::Loop(cycle));
+      return false;
+    }
+    let before = self.checked.replace(state.registers.clone());
+    if self.resume_at.take() != Some(state.pc) {
+      if let Some(index) = self.breakpoints.iter().position(|breakpoint| breakpoint.hit(state, before.as_deref())) {
+        self.stop = Some(Stop::Breakpoint(index));
+        return false;
       }
+    }
+    match self.steps_left {
+      Some(0) => {
+        self.stop = Some(Stop::Stepped);
+        return false;
+      },
+      Some(steps) => self.steps_left = Some(steps - 1),
+      None => {}
+    }
+    return true;
+  }
 
-      "jio" => {
-        let register = parts[1].chars().nth(0).unwrap().to_string();
-        let offset = parts[2].parse::<i32>().unwrap();
-        instructions.push(Instruction::Jio(register, offset));
+  fn after_execute(&mut self, state: &State, instr: &Instruction) {
+    self.counts[state.pc] += 1;
+    for reg in self.watches.iter() {
+      let (before, after) = (self.previous[reg.0], state.get(*reg));
+      if before != after {
+        println!("  watch {}: {} -> {} after {:>4}: {} (step {})", REGISTERS[reg.0], before, after, state.pc, instr, state.executed);
       }
+    }
+    self.previous.clone_from(&state.registers);
+  }
+}
 
-      _ => println!("Was unable to match line: {}", line),
+fn format_registers(state: &State) -> String {
+  return REGISTERS.iter().zip(state.registers.iter())
+    .map(|(name, value)| format!("{}={}", name, value))
+    .collect::<Vec<String>>().join(" ");
+}
+
+fn histogram(program: &[Instruction], counts: &[u64]) -> String {
+  let most = counts.iter().copied().max().unwrap_or(0).max(1);
+  let mut lines = String::new();
+  for (address, count) in counts.iter().enumerate() {
+    let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most) as usize);
+    lines.push_str(&format!("{:<40} {:>10} {}\n", disassemble_line(program, address), count, bar));
+  }
+  return lines;
+}
+
+fn describe_exit(exit: Exit, debugger: &Debugger, state: &State) -> String {
+  return match (exit, debugger.stop) {
+    (Exit::Stopped, Some(Stop::Loop(cycle))) => format!("never halts: pc {} with {} repeats every {} steps (seen at step {})",
+      state.pc, format_registers(state), cycle.period, cycle.seen_at),
+    (Exit::Stopped, Some(Stop::Breakpoint(index))) => format!("breakpoint {} ({}) at {}",
+      index, debugger.breakpoints[index], state.pc),
+    (Exit::Stopped, _) => format!("stopped at {}", state.pc),
+    (Exit::Halted, _) | (Exit::OutOfBounds(_), _) => format!("halted after {} steps with {}", state.executed, format_registers(state))
+  };
+}
+
+fn run_part(part: u32, program: &[Instruction], registers: Vec<Word>, show_histogram: bool) {
+  let mut machine = Machine::with_registers(program, registers);
+  let mut debugger = Debugger::new(program, &machine.state);
+  let exit = machine.run_with(&mut debugger);
+
+  match exit {
+    Exit::Stopped => println!("Part {}: {}", part, describe_exit(exit, &debugger, &machine.state)),
+    _ => println!("Part {}: {}", part, machine.state.get(Reg(1)))
+  }
+  if show_histogram {
+    println!("  {}", describe_exit(exit, &debugger, &machine.state));
+    print!("{}", histogram(program, &debugger.counts));
+  }
+}
+
+const DEBUGGER_HELP: &str = "commands:
+  s [n]        step one (or n) instructions
+  c            continue to the next breakpoint, loop or halt
+  b <spec>     break at an address or on a condition such as a==1
+  d <n>        delete breakpoint n
+  w <reg>      report every change to a register
+  r            show the registers
+  l            list the program around the pc
+  h            show the execution-count histogram
+  q            quit";
+
+fn list_around(program: &[Instruction], pc: usize) {
+  for address in pc.saturating_sub(3)..(pc + 4).min(program.len()) {
+    println!("{} {}", if address == pc { "=>" } else { "  " }, disassemble_line(program, address));
+  }
+}
+
+/* A command loop on stdin, so a session can also be scripted by piping commands in. */
+fn debug(program: &[Instruction], registers: Vec<Word>, breakpoints: Vec<Breakpoint>, watches: Vec<Reg>) {
+  let mut machine = Machine::with_registers(program, registers);
+  let mut debugger = Debugger::new(program, &machine.state);
+  debugger.breakpoints = breakpoints;
+  debugger.watches = watches;
+  let mut finished = false;
+
+  println!("{}", DEBUGGER_HELP);
+  list_around(program, machine.state.pc);
+  let stdin = io::stdin();
+  let mut lines = stdin.lock().lines();
+  loop {
+    print!("({}) ", format_registers(&machine.state));
+    io::stdout().flush().unwrap();
+    let line = match lines.next() {
+      Some(Ok(line)) => line,
+      _ => break
+    };
+    let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+    let argument = argument.trim();
+
+    match command {
+      "s" | "step" | "c" | "continue" => {
+        if finished {
+          println!("the program has halted");
+          continue;
+        }
+        let steps = match command {
+          "s" | "step" if argument.is_empty() => Some(1),
+          "s" | "step" => match argument.parse::<u64>() {
+            Ok(steps) => Some(steps),
+            Err(_) => {
+              println!("expected a number of steps but got {}", argument);
+              continue;
+            }
+          },
+          _ => None
+        };
+        debugger.resume(&machine.state, steps);
+        let exit = machine.run_with(&mut debugger);
+        finished = exit != Exit::Stopped;
+        if debugger.stop != Some(Stop::Stepped) { println!("{}", describe_exit(exit, &debugger, &machine.state)); }
+        if !finished { list_around(program, machine.state.pc); }
+      },
+      "b" | "break" => match Breakpoint::parse(argument) {
+        Ok(breakpoint) => {
+          debugger.breakpoints.push(breakpoint);
+          println!("breakpoint {}: {}", debugger.breakpoints.len() - 1, breakpoint);
+        },
+        Err(message) => println!("{}", message)
+      },
+      "d" | "delete" => match argument.parse::<usize>() {
+        Ok(index) if index < debugger.breakpoints.len() => println!("deleted {}", debugger.breakpoints.remove(index)),
+        _ => println!("no breakpoint {}", argument)
+      },
+      "w" | "watch" => match parse_register(argument) {
+        Some(reg) => debugger.watches.push(reg),
+        None => println!("unknown register {}", argument)
+      },
+      "r" | "regs" => println!("pc={} {} after {} steps", machine.state.pc, format_registers(&machine.state), machine.state.executed),
+      "l" | "list" => list_around(program, machine.state.pc),
+      "h" | "hist" => print!("{}", histogram(program, &debugger.counts)),
+      "q" | "quit" => break,
+      "" => {},
+      _ => println!("{}", DEBUGGER_HELP)
     }
   }
+}
 
-  println!("Part 1: {}", run_and_get_value_of_reg(&instructions, "b".to_string()));
-  println!("Part 2: {}", set_reg_run_and_get_value_of_reg(&instructions, "b".to_string()));
-}
\ No newline at end of file
+fn main() {
+  let args = Args::parse();
+  let path = args.value("--program").unwrap_or("./data/q23.txt");
+  let contents = fs::read_to_string(path)
+    .expect("Should have been able to read file");
+  let program = assemble(&contents).unwrap_or_else(|error| panic!("{}", error));
+
+  if args.flag("--disasm") { print!("{}", disassemble(&program)); }
+
+  if args.flag("--debug") {
+    let registers = match args.value("--part") {
+      Some("2") => vec![1, 0],
+      _ => vec![0, 0]
+    };
+    let breakpoints = args.values("--break").iter()
+      .map(|spec| Breakpoint::parse(spec).unwrap_or_else(|message| panic!("{}", message)))
+      .collect::<Vec<Breakpoint>>();
+    let watches = args.values("--watch").iter()
+      .map(|name| parse_register(name).unwrap_or_else(|| panic!("Unknown register: {}", name)))
+      .collect::<Vec<Reg>>();
+    debug(&program, registers, breakpoints, watches);
+    return;
+  }
+
+  let show_histogram = args.flag("--histogram");
+  run_part(1, &program, vec![0, 0], show_histogram);
+  run_part(2, &program, vec![1, 0], show_histogram);
+}
@@ -1,55 +1,219 @@
-use std::fs;
+use std::{fs, cmp::Reverse, collections::BinaryHeap};
+use aoc_common::cli::Args;
 
-fn get_group(
-  packages: &Vec<u64>, 
-  curr_group: &Vec<u64>, 
-  limit: u64) -> Vec<Vec<u64>>
-{ 
-  let sum = curr_group.iter().sum::<u64>();
-  if sum == limit { return vec![curr_group.clone()] }
+/*
+  fits[index][count][weight] says whether exactly `count` of the packages from
+  `index` on weigh `weight` between them.
+*/
+fn fits_table(weights: &[u64], target: u64) -> Vec<Vec<Vec<bool>>> {
+  let n = weights.len();
+  let target = target as usize;
+  let mut fits = vec![vec![vec![false; target + 1]; n + 1]; n + 1];
+  fits[n][0][0] = true;
+  for index in (0..n).rev() {
+    let weight = weights[index] as usize;
+    for count in 0..=n - index {
+      for total in 0..=target {
+        let take = count > 0 && total >= weight && fits[index + 1][count - 1][total - weight];
+        fits[index][count][total] = fits[index + 1][count][total] || take;
+      }
+    }
+  }
+  return fits;
+}
+
+/*
+  Every group weighing the target, fewest packages first and then by smallest
+  quantum entanglement. Within one size this is a best-first search over
+  partial groups keyed by their product so far, which only grows as packages
+  are added, so complete groups come off the heap in product order. The fits
+  table keeps every partial group on the heap completable.
+*/
+struct Candidates<'a> {
+  weights: &'a [u64],
+  target: u64,
+  fits: Vec<Vec<Vec<bool>>>,
+  size: usize,
+  heap: BinaryHeap<Reverse<(u128, Vec<usize>)>>
+}
 
-  if sum > limit { return vec![] }
+impl<'a> Candidates<'a> {
+  fn new(weights: &'a [u64], target: u64) -> Candidates<'a> {
+    return Candidates{weights, target, fits: fits_table(weights, target), size: 0, heap: BinaryHeap::new()};
+  }
+}
 
-  let mut next_packages = packages.clone();
-  let mut valid_groups: Vec<Vec<u64>> = Vec::new();
-  for package in packages.iter() {
-    let mut next_group = curr_group.clone();
-    next_group.push(*package);
-    next_packages.remove(0);
+impl<'a> Iterator for Candidates<'a> {
+  type Item = (u128, Vec<u64>);
 
-    let groups = get_group(&next_packages, &next_group, limit);
+  fn next(&mut self) -> Option<(u128, Vec<u64>)> {
+    loop {
+      let Reverse((product, chosen)) = match self.heap.pop() {
+        Some(node) => node,
+        None => {
+          if self.size == self.weights.len() { return None; }
+          self.size += 1;
+          if self.fits[0][self.size][self.target as usize] { self.heap.push(Reverse((1, Vec::new()))); }
+          continue;
+        }
+      };
+      if chosen.len() == self.size {
+        return Some((product, chosen.iter().map(|index| self.weights[*index]).collect()));
+      }
 
-    groups.iter().for_each(|group| valid_groups.push(group.clone()));
+      let sum = chosen.iter().map(|index| self.weights[*index]).sum::<u64>();
+      let left = self.size - chosen.len() - 1;
+      let start = chosen.last().map_or(0, |last| last + 1);
+      for index in start..self.weights.len() {
+        let weight = self.weights[index];
+        if sum + weight > self.target { break; }
+        if !self.fits[index + 1][left][(self.target - sum - weight) as usize] { continue; }
+        let mut next = chosen.clone();
+        next.push(index);
+        self.heap.push(Reverse((product * weight as u128, next)));
+      }
+    }
   }
+}
+
+/*
+  Splits `packages` into `groups` groups of `target` each, or shows it can't.
+  The heaviest package has to go somewhere, so only groups containing it are
+  tried, and a subset-sum table over the rest steers the search straight to
+  the groups that reach the target.
+*/
+fn partition(packages: &[u64], groups: usize, target: u64) -> Option<Vec<Vec<u64>>> {
+  if packages.iter().sum::<u64>() != target * groups as u64 { return None; }
+  if groups <= 1 { return Some(if groups == 1 { vec![packages.to_vec()] } else { Vec::new() }); }
 
-  return valid_groups;
+  let mut packages = packages.to_vec();
+  packages.sort_by(|a, b| b.cmp(a));
+  let (heaviest, rest) = (packages[0], &packages[1..]);
+  if heaviest > target { return None; }
+
+  let need = (target - heaviest) as usize;
+  let mut reach = vec![vec![false; need + 1]; rest.len() + 1];
+  reach[rest.len()][0] = true;
+  for index in (0..rest.len()).rev() {
+    for total in 0..=need {
+      let weight = rest[index] as usize;
+      reach[index][total] = reach[index + 1][total] || (total >= weight && reach[index + 1][total - weight]);
+    }
+  }
+  if !reach[0][need] { return None; }
+
+  let mut taken = vec![false; rest.len()];
+  return complete_group(rest, &reach, 0, need, &mut taken, groups, target).map(|mut found| {
+    found[0].insert(0, heaviest);
+    found
+  });
 }
 
-fn get_quantum_entanglement(packages: &Vec<u64>, num_groups: u64) -> u64 {
-  let weight_limit: u64 = packages.iter().sum::<u64>() / num_groups;
-  let mut groups = get_group(packages, &Vec::new(), weight_limit);
+/* The group marked in `taken` followed by a split of the packages left over. */
 
-  groups.sort_by(|a, b| a.len().cmp(&b.len()));
-  let min_len = groups[0].len();
-  let mut quantum_entanglements = groups
-    .iter()
-    .take_while(|group| group.len() == min_len)
-    .map(|group| group.iter().product::<u64>())
-    .collect::<Vec<u64>>();
+fn complete_group(rest: &[u64], reach: &[Vec<bool>], index: usize, need: usize, taken: &mut Vec<bool>,
+  groups: usize, target: u64) -> Option<Vec<Vec<u64>>>
+{
+  if need == 0 {
+    let marked = |wanted: bool| rest.iter().zip(taken.iter()).filter(|(_, taken)| **taken == wanted)
+      .map(|(weight, _)| *weight).collect::<Vec<u64>>();
+    let (group, leftover) = (marked(true), marked(false));
+    return partition(&leftover, groups - 1, target).map(|mut others| {
+      others.insert(0, group);
+      others
+    });
+  }
+  let weight = rest[index] as usize;
+  if weight <= need && reach[index + 1][need - weight] {
+    taken[index] = true;
+    let found = complete_group(rest, reach, index + 1, need - weight, taken, groups, target);
+    taken[index] = false;
+    if found.is_some() { return found; }
+  }
+  if reach[index + 1][need] { return complete_group(rest, reach, index + 1, need, taken, groups, target); }
+  return None;
+}
 
-  quantum_entanglements.sort();
+/* First groups the other packages can be balanced around, best first. */
+fn balanced_splits<'a>(packages: &'a [u64], num_groups: usize) -> impl Iterator<Item = (u128, Vec<Vec<u64>>)> + 'a {
+  let total = packages.iter().sum::<u64>();
+  let target = total / num_groups.max(1) as u64;
+  let candidates = if num_groups > 0 && total % num_groups as u64 == 0 { Some(Candidates::new(packages, target)) } else { None };
 
-  return quantum_entanglements[0];
+  return candidates.into_iter().flatten().filter_map(move |(product, group)| {
+    let mut rest = packages.to_vec();
+    for weight in group.iter() {
+      rest.remove(rest.iter().position(|package| package == weight).unwrap());
+    }
+    partition(&rest, num_groups - 1, target).map(|mut others| {
+      others.insert(0, group);
+      (product, others)
+    })
+  });
+}
+
+fn get_quantum_entanglement(packages: &[u64], num_groups: usize) -> Option<u128> {
+  return balanced_splits(packages, num_groups).next().map(|(product, _)| product);
+}
+
+fn report(part: u32, packages: &[u64], num_groups: usize, list: usize) {
+  match get_quantum_entanglement(packages, num_groups) {
+    Some(product) => println!("Part {}: {}", part, product),
+    None => println!("Part {}: the packages cannot be split into {} equal groups", part, num_groups)
+  }
+  for (product, groups) in balanced_splits(packages, num_groups).take(list) {
+    println!("  {:>16}  {:?}", product, groups);
+  }
 }
 
 fn main() {
+  let args = Args::parse();
   let contents = fs::read_to_string("./data/q24.txt")
     .expect("Should have been able to read file");
-  let packages = contents
-    .split("\n")
+  let mut packages = contents
+    .lines()
+    .filter(|line| !line.is_empty())
     .map(|package| package.parse::<u64>().unwrap())
     .collect::<Vec<u64>>();
+  packages.sort();
+  let list = args.value("--list").map_or(0, |n| n.parse::<usize>().unwrap());
+
+  report(1, &packages, 3, list);
+  report(2, &packages, 4, list);
+  if let Some(groups) = args.value("--groups") {
+    report(3, &packages, groups.parse::<usize>().unwrap(), list);
+  }
+}
 
-  println!("Part 1: {}", get_quantum_entanglement(&packages, 3));
-  println!("Part 2: {}", get_quantum_entanglement(&packages, 4));
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_balanced(packages: &[u64], groups: &[Vec<u64>], num_groups: usize) {
+    let target = packages.iter().sum::<u64>() / num_groups as u64;
+    assert_eq!(groups.len(), num_groups, "{:?}", groups);
+    for group in groups.iter() {
+      assert_eq!(group.iter().sum::<u64>(), target, "{:?}", groups);
+    }
+    let mut used = groups.concat();
+    used.sort();
+    assert_eq!(used, packages);
+  }
+
+  #[test]
+  fn every_group_weighs_the_target() {
+    let packages = [1, 2, 3, 4, 5, 7, 8, 9, 10, 11];
+    for num_groups in [2, 3, 4] {
+      let splits = balanced_splits(&packages, num_groups).take(20).collect::<Vec<(u128, Vec<Vec<u64>>)>>();
+      assert!(!splits.is_empty());
+      for (product, groups) in splits.iter() {
+        assert_eq!(*product, groups[0].iter().map(|weight| *weight as u128).product::<u128>());
+        assert_balanced(&packages, groups, num_groups);
+      }
+      assert_balanced(&packages, &partition(&packages, num_groups, 60 / num_groups as u64).unwrap(), num_groups);
+    }
+    assert_eq!(get_quantum_entanglement(&packages, 3), Some(99));
+    assert_eq!(get_quantum_entanglement(&packages, 4), Some(44));
+    assert_eq!(partition(&[5, 5, 2], 2, 6), None);
+  }
+}
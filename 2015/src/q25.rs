@@ -1,55 +1,247 @@
-use std::fs;
+use std::{fs, collections::HashMap};
 use regex::Regex;
+use aoc_common::cli::Args;
 
-fn get_value(row: &i32, col: &i32) -> u64{
-  let mut curr_row: i32 = 1;
-  let mut curr_col: i32 = 1;
-  let mut max_row: i32 = 1;
-  let mut prev_val: u64 = 20151125;
-  let mut curr_val: u64;
-  let multiplier: u64 = 252533;
-  let modulo: u64 = 33554393;
-
-  loop {
-    curr_row -= 1;
-    curr_col += 1;
-
-    if curr_row == 0 {
-      max_row += 1;
-      curr_row = max_row;
-      curr_col = 1;
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+  return (a as u128 * b as u128 % modulus as u128) as u64;
+}
+
+fn pow_mod(base: u64, mut exponent: u128, modulus: u64) -> u64 {
+  let mut result = 1 % modulus;
+  let mut base = base % modulus;
+  while exponent > 0 {
+    if exponent & 1 == 1 { result = mul_mod(result, base, modulus); }
+    base = mul_mod(base, base, modulus);
+    exponent >>= 1;
+  }
+  return result;
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+  return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+fn inverse_mod(a: u64, modulus: u64) -> Option<u64> {
+  let (mut old_r, mut r) = (a as i128 % modulus as i128, modulus as i128);
+  let (mut old_s, mut s) = (1_i128, 0_i128);
+  while r != 0 {
+    let quotient = old_r / r;
+    (old_r, r) = (r, old_r - quotient * r);
+    (old_s, s) = (s, old_s - quotient * s);
+  }
+  if old_r != 1 { return None; }
+  return Some(old_s.rem_euclid(modulus as i128) as u64);
+}
+
+/* Trial division, which is instant for moduli up to around 10^12. */
+fn prime_factors(mut n: u64) -> Vec<u64> {
+  let mut factors = Vec::new();
+  let mut p = 2;
+  while p <= n / p {
+    if n.is_multiple_of(p) {
+      factors.push(p);
+      while n.is_multiple_of(p) { n /= p; }
     }
+    p += if p == 2 { 1 } else { 2 };
+  }
+  if n > 1 { factors.push(n); }
+  return factors;
+}
+
+fn totient(n: u64) -> u64 {
+  return prime_factors(n).iter().fold(n, |phi, p| phi / p * (p - 1));
+}
+
+/* The smallest k > 0 with base^k = 1, for a base coprime to the modulus. */
+fn order(base: u64, modulus: u64) -> u64 {
+  let mut order = totient(modulus);
+  for p in prime_factors(order) {
+    while order.is_multiple_of(p) && pow_mod(base, (order / p) as u128, modulus) == 1 { order /= p; }
+  }
+  return order;
+}
+
+/*
+  The smallest k below `period` with base^k = target, by baby-step giant-step:
+  with s about the square root of the period, every such k is i * s + j for
+  some j < s, so the s values base^j are tabled and the target is walked down
+  by base^-s until it lands in the table.
+*/
+fn discrete_log(base: u64, target: u64, modulus: u64, period: u64) -> Option<u64> {
+  let step = (period as u128).isqrt() as u64 + 1;
+
+  let mut baby: HashMap<u64, u64> = HashMap::new();
+  let mut power = 1 % modulus;
+  for j in 0..step {
+    baby.entry(power).or_insert(j);
+    power = mul_mod(power, base, modulus);
+  }
+
+  let giant = inverse_mod(pow_mod(base, step as u128, modulus), modulus)?;
+  let mut gamma = target % modulus;
+  for i in 0..=period / step {
+    if let Some(j) = baby.get(&gamma) {
+      let k = i * step + *j;
+      if k < period { return Some(k); }
+    }
+    gamma = mul_mod(gamma, giant, modulus);
+  }
+  return None;
+}
+
+/*
+  Codes fill the grid along its diagonals, and diagonal d (counting from 1)
+  starts after the d(d - 1) / 2 cells of the diagonals above it, so the code at
+  (row, column) is `first * multiplier^index`.
+*/
+struct CodeGrid {
+  first: u64,
+  multiplier: u64,
+  modulus: u64
+}
+
+impl CodeGrid {
+  fn diagonal(row: u64, col: u64) -> u128 {
+    return row as u128 + col as u128 - 1;
+  }
+
+  /* The index, if it fits: far enough out in the grid it passes 2^128. */
+  fn index(row: u64, col: u64) -> Option<u128> {
+    let d = CodeGrid::diagonal(row, col);
+    let (even, other) = if d.is_multiple_of(2) { (d / 2, d - 1) } else { (d, (d - 1) / 2) };
+    return even.checked_mul(other)?.checked_add(col as u128 - 1);
+  }
+
+  fn index_mod(row: u64, col: u64, n: u64) -> u128 {
+    let n = n as u128;
+    let d = CodeGrid::diagonal(row, col);
+    let (even, other) = if d.is_multiple_of(2) { (d / 2, d - 1) } else { (d, (d - 1) / 2) };
+    return ((even % n) * (other % n) % n + (col as u128 - 1) % n) % n;
+  }
 
-    curr_val = (prev_val * multiplier) % modulo;
+  /* Index to (row, column), the inverse of `index`. */
+  fn position(index: u128) -> (u128, u128) {
+    let d = (1 + 8 * index).isqrt().div_ceil(2);
+    let col = index - d * (d - 1) / 2 + 1;
+    return (d - col + 1, col);
+  }
+
+  /*
+    Indices too big for a u128 are cut down using Euler's theorem in the form
+    that holds whether or not the multiplier shares factors with the modulus:
+    for exponents of at least log2(modulus), m^e = m^(e mod phi + phi).
+  */
+  fn code_at(&self, row: u64, col: u64) -> u64 {
+    let exponent = match CodeGrid::index(row, col) {
+      Some(index) => index,
+      None => {
+        let phi = totient(self.modulus);
+        CodeGrid::index_mod(row, col, phi) + phi as u128
+      }
+    };
+    return self.code_at_index(exponent);
+  }
 
-    if curr_row == *row && curr_col == *col {
-      break;
+  fn code_at_index(&self, index: u128) -> u64 {
+    return mul_mod(self.first % self.modulus, pow_mod(self.multiplier, index, self.modulus), self.modulus);
+  }
+
+  /*
+    The modulus as n1 * n2, where n1 takes every prime power p^e of it with p
+    dividing the multiplier, along with the largest such e. From that index
+    on, every code is 0 mod n1, and mod n2 the multiplier is invertible.
+  */
+  fn split(&self) -> (u64, u64, u64) {
+    let (mut tail, mut n1, mut n2) = (0, 1, self.modulus);
+    for p in prime_factors(self.modulus) {
+      if !self.multiplier.is_multiple_of(p) { continue; }
+      let mut exponent = 0;
+      while n2.is_multiple_of(p) {
+        n2 /= p;
+        n1 *= p;
+        exponent += 1;
+      }
+      tail = tail.max(exponent);
     }
+    return (tail, n1, n2);
+  }
 
-    prev_val = curr_val;
+  /*
+    How many codes pass before the sequence repeats, once past the tail. Only
+    n2 / gcd(first, n2) of the modulus is left for the multiplier to cycle.
+  */
+  fn period(&self) -> u64 {
+    let (_, _, n2) = self.split();
+    return order(self.multiplier, n2 / gcd(self.first % n2, n2));
   }
 
-  return curr_val;
+  /*
+    The first index holding `code`. The codes before the tail are checked one
+    by one; past it a code is first * m^k, which is 0 mod n1, and mod n2 the
+    first factor can be divided out to leave a discrete log on the cycle.
+  */
+  fn find(&self, code: u64) -> Option<u128> {
+    if code >= self.modulus { return None; }
+    let (tail, n1, n2) = self.split();
+    let mut value = self.first % self.modulus;
+    for index in 0..tail {
+      if value == code { return Some(index as u128); }
+      value = mul_mod(value, self.multiplier, self.modulus);
+    }
+    if !code.is_multiple_of(n1) { return None; }
+
+    let common = gcd(self.first % n2, n2);
+    if !(code % n2).is_multiple_of(common) { return None; }
+    let reduced = n2 / common;
+    let target = mul_mod(code % n2 / common, inverse_mod(self.first % n2 / common, reduced)?, reduced);
+    let period = self.period() as u128;
+    let log = discrete_log(self.multiplier % reduced, target, reduced, period as u64)? as u128;
+    let tail = tail as u128;
+    return Some(if log >= tail { log } else { log + (tail - log).div_ceil(period) * period });
+  }
 }
 
 fn main() {
-  let contents = fs::read_to_string("./data/q25.txt")
-    .expect("Should have been able to read file");
-  let re = Regex::new("To continue, please consult the code grid \
-    in the manual.  Enter the code at row ([\\d]+), column ([\\d]+).").unwrap();
-  let captures = re.captures(&contents);
-  let mut row: i32 = 0;
-  let mut col: i32 = 0;
-
-  match captures {
-    Some(matched_groups) => {
-      row = matched_groups[1].parse::<i32>().unwrap();
-      col = matched_groups[2].parse::<i32>().unwrap();
-    }
+  let args = Args::parse();
+  let grid = CodeGrid{
+    first: args.value("--first").map_or(20151125, |first| first.parse::<u64>().unwrap()),
+    multiplier: args.value("--multiplier").map_or(252533, |multiplier| multiplier.parse::<u64>().unwrap()),
+    modulus: args.value("--modulus").map_or(33554393, |modulus| modulus.parse::<u64>().unwrap())
+  };
+  if grid.modulus < 2 { panic!("The modulus has to be at least 2"); }
 
-    None => println!("Was unable to match line: {}", contents),
+  if let Some(code) = args.value("--find") {
+    let code = code.parse::<u64>().unwrap();
+    match grid.find(code) {
+      Some(index) => {
+        let (row, col) = CodeGrid::position(index);
+        println!("{} first appears at row {}, column {} (code number {})", code, row, col, index + 1);
+        let period = grid.period();
+        if grid.code_at_index(index + period as u128) == code {
+          println!("  and again every {} codes", period);
+        } else {
+          println!("  and never again");
+        }
+      },
+      None => println!("{} never appears in the grid", code)
+    }
+    return;
   }
 
-  println!("Part 1: {}", get_value(&row, &col));
+  let (row, col) = match (args.value("--row"), args.value("--col")) {
+    (Some(row), Some(col)) => (row.parse::<u64>().unwrap(), col.parse::<u64>().unwrap()),
+    _ => {
+      let contents = fs::read_to_string("./data/q25.txt")
+        .expect("Should have been able to read file");
+      let re = Regex::new("row ([\\d]+), column ([\\d]+)").unwrap();
+      match re.captures(&contents) {
+        Some(matched_groups) => (matched_groups[1].parse::<u64>().unwrap(), matched_groups[2].parse::<u64>().unwrap()),
+        None => panic!("Was unable to match line: {}", contents)
+      }
+    }
+  };
+  if row == 0 || col == 0 { panic!("Rows and columns are numbered from 1"); }
+
+  println!("Part 1: {}", grid.code_at(row, col));
   println!("Part 2: {}", 0);
-}
\ No newline at end of file
+}
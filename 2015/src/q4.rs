@@ -1,24 +1,122 @@
-use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
 use md5;
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
-fn find_integer_hash_with_leading_zeroes(secret_key: &str, number_of_zeroes: usize) -> u32 {
-  let mut number: u32 = 1;
+const CHUNK_SIZE: u64 = 16384;
+
+fn leading_zero_nibbles(number_of_zeroes: usize) -> impl Fn(&[u8; 16]) -> bool + Sync {
+  if number_of_zeroes > 32 { panic!("An MD5 digest has only 32 hex digits, not {}", number_of_zeroes); }
+  return move |digest: &[u8; 16]| {
+    let full_bytes = number_of_zeroes / 2;
+    digest[..full_bytes].iter().all(|byte| *byte == 0) &&
+      (number_of_zeroes.is_multiple_of(2) || digest[full_bytes] >> 4 == 0)
+  };
+}
+
+fn hex_prefix(prefix: &str) -> impl Fn(&[u8; 16]) -> bool + Sync {
+  let nibbles = prefix.chars()
+    .map(|c| c.to_digit(16).unwrap_or_else(|| panic!("Not a hex digit: {}", c)) as u8)
+    .collect::<Vec<u8>>();
+  if nibbles.len() > 32 { panic!("Prefix {} is longer than an MD5 digest", prefix); }
+  return move |digest: &[u8; 16]| {
+    nibbles.iter().enumerate().all(|(i, nibble)| {
+      let byte = digest[i / 2];
+      let digest_nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0xf };
+      digest_nibble == *nibble
+    })
+  };
+}
+
+fn write_decimal(mut number: u64, buffer: &mut [u8; 20]) -> &[u8] {
+  let mut index = buffer.len();
   loop {
-    let plaintext = format!("{}{}", secret_key, number.to_string());
-    let hash_string = format!("{:X}", md5::compute(plaintext));
-    let num_leading_zeroes: usize = hash_string.chars()
-      .take_while(|c: &char| *c == '0')
-      .collect::<Vec<char>>()
-      .len();
-    if num_leading_zeroes >= number_of_zeroes { break; }
-    number += 1;
+    index -= 1;
+    buffer[index] = b'0' + (number % 10) as u8;
+    number /= 10;
+    if number == 0 { break; }
+  }
+  return &buffer[index..];
+}
+
+/*
+  Nonces are handed out in fixed-size chunks in increasing order, so once some
+  worker finds a hit every smaller nonce is either already checked or sitting
+  in a chunk another worker is still finishing. Workers only skip chunks that
+  start past the best hit, which keeps the answer the smallest nonce.
+*/
+fn find_nonce<P>(secret_key: &str, predicate: P, threads: usize, ctx: &Context) -> Result<u64, Cancelled>
+  where P: Fn(&[u8; 16]) -> bool + Sync
+{
+  let mut prefix = md5::Context::new();
+  prefix.consume(secret_key.as_bytes());
+
+  let next_chunk = AtomicU64::new(0);
+  let best = AtomicU64::new(u64::MAX);
+  let cancelled = AtomicBool::new(false);
+
+  thread::scope(|scope| {
+    for _ in 0..threads {
+      scope.spawn(|| {
+        let mut buffer = [0_u8; 20];
+        loop {
+          let start = next_chunk.fetch_add(1, Ordering::Relaxed) * CHUNK_SIZE + 1;
+          if start >= best.load(Ordering::Relaxed) || cancelled.load(Ordering::Relaxed) { return; }
+          if ctx.progress("nonces tried", start, None).is_err() {
+            cancelled.store(true, Ordering::Relaxed);
+            return;
+          }
+
+          for nonce in start..start + CHUNK_SIZE {
+            let mut hasher = prefix.clone();
+            hasher.consume(write_decimal(nonce, &mut buffer));
+            if predicate(&hasher.compute().0) {
+              best.fetch_min(nonce, Ordering::Relaxed);
+              break;
+            }
+          }
+        }
+      });
+    }
+  });
+
+  if cancelled.load(Ordering::Relaxed) { ctx.check()?; }
+  return match best.load(Ordering::Relaxed) {
+    u64::MAX => panic!("The nonce search stopped without finding a match"),
+    nonce => Ok(nonce)
+  };
+}
+
+fn find_integer_hash_with_leading_zeroes(secret_key: &str, number_of_zeroes: usize, threads: usize, ctx: &Context)
+  -> Result<u64, Cancelled>
+{
+  return find_nonce(secret_key, leading_zero_nibbles(number_of_zeroes), threads, ctx);
+}
+
+fn solve(contents: &str, ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
+  let args = Args::parse();
+  let secret_key = contents.trim();
+  let threads = match args.value("--threads") {
+    Some(threads) => match threads.parse::<usize>() {
+      Ok(threads) if threads >= 1 => threads,
+      _ => panic!("--threads expects a count of at least 1, got {}", threads)
+    },
+    None => thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+  };
+
+  let mut answers: Vec<Answer> = vec![
+    find_integer_hash_with_leading_zeroes(secret_key, 5, threads, ctx)?.into(),
+    find_integer_hash_with_leading_zeroes(secret_key, 6, threads, ctx)?.into()
+  ];
+  if let Some(prefix) = args.value("--prefix") {
+    answers.push(find_nonce(secret_key, hex_prefix(prefix), threads, ctx)?.into());
   }
-  return number;
+  return Ok(answers);
 }
 
 fn main() {
-  let secret_key = fs::read_to_string("./data/q4.txt")
-    .expect("Should have been able to read file");
-  println!("Part 1: {}", find_integer_hash_with_leading_zeroes(&secret_key, 5));
-  println!("Part 2: {}", find_integer_hash_with_leading_zeroes(&secret_key, 6));
-}
\ No newline at end of file
+  runner::run("q4", solve);
+}
@@ -1,76 +1,240 @@
-use std::fs;
-use std::collections::HashMap;
-
-fn count_nice_strings(strings: &Vec<&str>) -> u32 {
-  let nice_strings: u32 = strings.iter()
-    .map(|string: &&str| {
-      let char_vec: Vec<char> = string.chars().collect::<Vec<char>>();
-      let mut num_vowels: u32 = 0;
-      let mut double_letters: u32 = 0;
-      let mut has_forbidden_string: bool = false;
-      for i in 0..char_vec.len() {
-        match char_vec[i] {
-          'a' | 'e' | 'i' | 'o' | 'u' => num_vowels += 1,
-          _ => ()
-        }
-        if i != char_vec.len() - 1 {
-          if char_vec[i] == char_vec[i + 1] { double_letters += 1 }
-          let window: String = format!("{}{}", char_vec[i], char_vec[i + 1]);
-          match window.as_str() {
-            "ab" | "cd" | "pq" | "xy" => has_forbidden_string = true,
-            _ => ()
-          }
-        }
-      }
-      if num_vowels >= 3 && double_letters >= 1 && !has_forbidden_string { 1 } else { 0 }
-    })
-    .sum();
-  return nice_strings;
-}
-
-fn count_nice_strings_new_way(strings: &Vec<&str>) -> u32 {
-  let mut nice_strings = 0;
-
-  for string in strings.iter() {
-    let char_vec = string.chars().collect::<Vec<char>>();
-    let mut has_repeated_letters = false;
-    let mut double_letters: HashMap<String, usize> = HashMap::new();
-    for i in 0..char_vec.len() - 1 {
-      let window = format!("{}{}", char_vec[i], char_vec[i + 1]);
-      if double_letters.contains_key(&window) {
-        let first_seen_index = double_letters.get(&window).unwrap();
-        if i - first_seen_index > 1 {
-          has_repeated_letters = true;
-          break;
-        }
-      } else {
-        double_letters.insert(window, i);
-      }
+use std::{fs, collections::{HashMap, VecDeque}, io::{BufRead, BufReader}};
+use aoc_common::cli::Args;
+
+/*
+  Used when there is no `--rules` file. Each `[section]` is a rule set and a
+  string is nice when every rule in the set accepts it. A rule is one of
+    at-least N of LETTERS   N or more of the letters, counting repeats
+    repeat gap N            some letter comes back with N letters in between
+    contains S1 S2 ...      one of the substrings appears
+    pair-twice              some pair of letters appears twice without overlapping
+  and rules combine with `not RULE` and `RULE | RULE`.
+*/
+const DEFAULT_RULES: &str = "[Part 1]
+three vowels: at-least 3 of aeiou
+double letter: repeat gap 0
+no naughty pairs: not contains ab cd pq xy
+
+[Part 2]
+pair twice: pair-twice
+letter sandwich: repeat gap 1
+";
+
+/* A single pass over a string, one character at a time. */
+trait Automaton {
+  fn feed(&mut self, c: char);
+  fn accepts(&self) -> bool;
+}
+
+struct CountOf {
+  letters: Vec<char>,
+  min: usize,
+  count: usize
+}
+
+impl Automaton for CountOf {
+  fn feed(&mut self, c: char) {
+    if self.letters.contains(&c) { self.count += 1; }
+  }
+
+  fn accepts(&self) -> bool {
+    return self.count >= self.min;
+  }
+}
+
+/* Keeps only the last `gap + 1` characters, the furthest back a match can reach. */
+struct Repeat {
+  gap: usize,
+  recent: VecDeque<char>,
+  found: bool
+}
+
+impl Automaton for Repeat {
+  fn feed(&mut self, c: char) {
+    if self.recent.len() == self.gap + 1 {
+      if self.recent[0] == c { self.found = true; }
+      self.recent.pop_front();
     }
+    self.recent.push_back(c);
+  }
 
-    if !has_repeated_letters { continue }
+  fn accepts(&self) -> bool {
+    return self.found;
+  }
+}
 
-    let mut has_aba = false;
+struct Contains {
+  patterns: Vec<Vec<char>>,
+  window: VecDeque<char>,
+  longest: usize,
+  found: bool
+}
 
-    for i in 0..char_vec.len() - 2 {
-      if char_vec[i] == char_vec[i + 2] {
-        has_aba = true;
-        break;
-      }
+impl Automaton for Contains {
+  fn feed(&mut self, c: char) {
+    if self.window.len() == self.longest { self.window.pop_front(); }
+    self.window.push_back(c);
+    let window = &self.window;
+    self.found |= self.patterns.iter().any(|pattern| pattern.len() <= window.len()
+      && window.iter().skip(window.len() - pattern.len()).eq(pattern.iter()));
+  }
+
+  fn accepts(&self) -> bool {
+    return self.found;
+  }
+}
+
+/* Remembers where each pair first ended; a later copy has to end two or more characters after it. */
+struct PairTwice {
+  previous: Option<char>,
+  first_end: HashMap<(char, char), usize>,
+  index: usize,
+  found: bool
+}
+
+impl Automaton for PairTwice {
+  fn feed(&mut self, c: char) {
+    if let Some(previous) = self.previous {
+      let first_end = *self.first_end.entry((previous, c)).or_insert(self.index);
+      if self.index - first_end >= 2 { self.found = true; }
     }
+    self.previous = Some(c);
+    self.index += 1;
+  }
+
+  fn accepts(&self) -> bool {
+    return self.found;
+  }
+}
+
+struct Not(Box<dyn Automaton>);
 
-    if !has_aba { continue }
-    nice_strings += 1;
+impl Automaton for Not {
+  fn feed(&mut self, c: char) {
+    self.0.feed(c);
   }
 
-  return nice_strings;
+  fn accepts(&self) -> bool {
+    return !self.0.accepts();
+  }
+}
+
+struct Any(Vec<Box<dyn Automaton>>);
+
+impl Automaton for Any {
+  fn feed(&mut self, c: char) {
+    self.0.iter_mut().for_each(|automaton| automaton.feed(c));
+  }
+
+  fn accepts(&self) -> bool {
+    return self.0.iter().any(|automaton| automaton.accepts());
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Rule {
+  AtLeast(usize, Vec<char>),
+  Repeat(usize),
+  Contains(Vec<String>),
+  PairTwice,
+  Not(Box<Rule>),
+  Any(Vec<Rule>)
+}
+
+impl Rule {
+  fn parse(text: &str) -> Rule {
+    let alternatives = text.split('|').map(|alternative| alternative.trim()).collect::<Vec<&str>>();
+    if alternatives.len() > 1 { return Rule::Any(alternatives.iter().map(|alternative| Rule::parse(alternative)).collect()); }
+
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    return match words.as_slice() {
+      ["not", ..] => Rule::Not(Box::new(Rule::parse(&words[1..].join(" ")))),
+      ["at-least", n, "of", letters] => Rule::AtLeast(n.parse::<usize>().unwrap(), letters.chars().collect()),
+      ["repeat", "gap", n] => Rule::Repeat(n.parse::<usize>().unwrap()),
+      ["contains", patterns @ ..] if !patterns.is_empty() => Rule::Contains(patterns.iter().map(|p| p.to_string()).collect()),
+      ["pair-twice"] => Rule::PairTwice,
+      _ => panic!("Was unable to match rule: {}", text)
+    };
+  }
+
+  fn start(&self) -> Box<dyn Automaton> {
+    return match self {
+      Rule::AtLeast(min, letters) => Box::new(CountOf{letters: letters.clone(), min: *min, count: 0}),
+      Rule::Repeat(gap) => Box::new(Repeat{gap: *gap, recent: VecDeque::new(), found: false}),
+      Rule::Contains(patterns) => Box::new(Contains{
+        patterns: patterns.iter().map(|pattern| pattern.chars().collect()).collect(),
+        window: VecDeque::new(),
+        longest: patterns.iter().map(|pattern| pattern.chars().count()).max().unwrap_or(0),
+        found: false
+      }),
+      Rule::PairTwice => Box::new(PairTwice{previous: None, first_end: HashMap::new(), index: 0, found: false}),
+      Rule::Not(rule) => Box::new(Not(rule.start())),
+      Rule::Any(rules) => Box::new(Any(rules.iter().map(|rule| rule.start()).collect()))
+    };
+  }
+}
+
+struct RuleSet {
+  name: String,
+  rules: Vec<(String, Rule)>
+}
+
+impl RuleSet {
+  /* The name of the first rule the string breaks, or None if it is nice. */
+  fn first_failure(&self, string: &str) -> Option<&str> {
+    let mut automata = self.rules.iter().map(|(_, rule)| rule.start()).collect::<Vec<Box<dyn Automaton>>>();
+    for c in string.chars() {
+      automata.iter_mut().for_each(|automaton| automaton.feed(c));
+    }
+    return automata.iter().zip(self.rules.iter())
+      .find(|(automaton, _)| !automaton.accepts())
+      .map(|(_, (name, _))| name.as_str());
+  }
+}
+
+fn parse_rule_sets(contents: &str) -> Vec<RuleSet> {
+  let mut sets: Vec<RuleSet> = Vec::new();
+  for line in contents.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+    if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+      sets.push(RuleSet{name: name.to_string(), rules: Vec::new()});
+      continue;
+    }
+    let (name, rule) = line.split_once(':').unwrap_or_else(|| panic!("Expected `name: rule` but got {}", line));
+    if sets.is_empty() { sets.push(RuleSet{name: "Rules".to_string(), rules: Vec::new()}); }
+    sets.last_mut().unwrap().rules.push((name.trim().to_string(), Rule::parse(rule)));
+  }
+  return sets;
+}
+
+/* One pass over the word list, checking every rule set as each string goes by. */
+fn count_nice_strings(reader: impl BufRead, sets: &[RuleSet], explain: bool) -> Vec<u32> {
+  let mut nice = vec![0; sets.len()];
+  for line in reader.lines() {
+    let line = line.expect("Should have been able to read line");
+    let string = line.trim();
+    if string.is_empty() { continue; }
+    for (index, set) in sets.iter().enumerate() {
+      match set.first_failure(string) {
+        None => nice[index] += 1,
+        Some(rule) => if explain { println!("  {}: {} fails {}", set.name, string, rule); }
+      }
+    }
+  }
+  return nice;
 }
 
 fn main() {
-  let contents = fs::read_to_string("./data/q5.txt")
+  let args = Args::parse();
+  let rules = match args.value("--rules") {
+    Some(path) => fs::read_to_string(path).expect("Should have been able to read file"),
+    None => DEFAULT_RULES.to_string()
+  };
+  let sets = parse_rule_sets(&rules);
+  let file = fs::File::open(args.value("--words").unwrap_or("./data/q5.txt"))
     .expect("Should have been able to read file");
-  let strings: Vec<&str> = contents.split("\n").collect::<Vec<&str>>();
 
-  println!("Part 1: {}", count_nice_strings(&strings));
-  println!("Part 2: {}", count_nice_strings_new_way(&strings));
-}
\ No newline at end of file
+  let nice = count_nice_strings(BufReader::new(file), &sets, args.flag("--explain"));
+  for (set, count) in sets.iter().zip(nice.iter()) {
+    println!("{}: {}", set.name, count);
+  }
+}
@@ -1,128 +1,249 @@
 use std::fs;
 use regex::Regex;
+use aoc_common::cli::Args;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+  TurnOn,
+  TurnOff,
+  Toggle
+}
+
+/* Corners are inclusive, as in the puzzle. */
 #[derive(Debug)]
 struct Instruction {
-  instruction: String,
-  lower_x: usize,
-  lower_y: usize,
-  upper_x: usize,
-  upper_y: usize,
+  action: Action,
+  lower_x: u64,
+  lower_y: u64,
+  upper_x: u64,
+  upper_y: u64
+}
+
+/*
+  What a run of instructions does to one light. Doing one effect and then the
+  next is itself an effect, so every light only needs the combined effect of
+  the instructions that covered it, applied to a light that starts off.
+*/
+trait Monoid: Copy {
+  fn identity() -> Self;
+  fn then(self, next: Self) -> Self;
+}
+
+trait Semantics {
+  type Effect: Monoid;
+
+  fn effect(&self, action: Action) -> Self::Effect;
+
+  /* What one light shows after `effect`. Lights no instruction touches show `value(identity)`, taken to be 0. */
+  fn value(&self, effect: Self::Effect) -> u128;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Switch {
+  Keep,
+  Set(bool),
+  Flip
 }
 
-impl Instruction {
-  fn get_x_range(&self) -> std::ops::Range<usize> {
-    return self.lower_x..self.upper_x + 1;
+impl Monoid for Switch {
+  fn identity() -> Switch {
+    return Switch::Keep;
   }
 
-  fn get_y_range(&self) -> std::ops::Range<usize> {
-    return self.lower_y..self.upper_y + 1;
+  fn then(self, next: Switch) -> Switch {
+    return match (self, next) {
+      (first, Switch::Keep) => first,
+      (_, Switch::Set(on)) => Switch::Set(on),
+      (Switch::Keep, Switch::Flip) => Switch::Flip,
+      (Switch::Set(on), Switch::Flip) => Switch::Set(!on),
+      (Switch::Flip, Switch::Flip) => Switch::Keep
+    };
   }
 }
 
-fn turn_on_lights(lights: &mut Vec<Vec<bool>>, instruction: &Instruction) {
-  for y in instruction.get_y_range() {
-    for x in instruction.get_x_range() {
-      lights[y][x] = true;
-    }
+struct OnOff;
+
+impl Semantics for OnOff {
+  type Effect = Switch;
+
+  fn effect(&self, action: Action) -> Switch {
+    return match action {
+      Action::TurnOn => Switch::Set(true),
+      Action::TurnOff => Switch::Set(false),
+      Action::Toggle => Switch::Flip
+    };
   }
-}
 
-fn turn_off_lights(lights: &mut Vec<Vec<bool>>, instruction: &Instruction) {
-  for y in instruction.get_y_range() {
-    for x in instruction.get_x_range() {
-      lights[y][x] = false;
-    }
+  fn value(&self, effect: Switch) -> u128 {
+    return (effect == Switch::Set(true) || effect == Switch::Flip) as u128;
   }
 }
 
-fn toggle_lights(lights: &mut Vec<Vec<bool>>, instruction: &Instruction) {
-  for y in instruction.get_y_range() {
-    for x in instruction.get_x_range() {
-      lights[y][x] = !lights[y][x];
-    }
+/* Brightness b becomes max(b + add, floor); two of these in a row are again of that form. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Dimmer {
+  add: i64,
+  floor: i64
+}
+
+impl Monoid for Dimmer {
+  fn identity() -> Dimmer {
+    return Dimmer{add: 0, floor: i64::MIN};
+  }
+
+  fn then(self, next: Dimmer) -> Dimmer {
+    return Dimmer{add: self.add + next.add, floor: self.floor.saturating_add(next.add).max(next.floor)};
   }
 }
 
-fn run_and_count_lights_on(instructions: &Vec<Instruction>) -> u32 {
-  let mut lights: Vec<Vec<bool>> = vec![vec![false; 1000]; 1000];
+struct Brightness;
 
-  for instruction in instructions.iter() {
-    match instruction.instruction.as_str() {
-      "turn on" => turn_on_lights(&mut lights, &instruction),
-      "turn off" => turn_off_lights(&mut lights, &instruction),
-      "toggle" => toggle_lights(&mut lights, &instruction),
-      _ => ()
-    }
+impl Semantics for Brightness {
+  type Effect = Dimmer;
+
+  fn effect(&self, action: Action) -> Dimmer {
+    return match action {
+      Action::TurnOn => Dimmer{add: 1, floor: 0},
+      Action::TurnOff => Dimmer{add: -1, floor: 0},
+      Action::Toggle => Dimmer{add: 2, floor: 0}
+    };
+  }
+
+  fn value(&self, effect: Dimmer) -> u128 {
+    return effect.add.max(effect.floor).max(0) as u128;
   }
+}
 
-  return lights.iter().fold(0, |acc, c|
-    acc + c.iter().fold(0, |acc1, val|
-      acc1 + (*val as u32)));
+/* Brightness b becomes `mul * b + add`, saturating rather than overflowing. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Linear {
+  mul: u128,
+  add: u128
 }
 
-fn turn_up_light_brightness(lights: &mut Vec<Vec<u32>>, instruction: &Instruction) {
-  for y in instruction.get_y_range() {
-    for x in instruction.get_x_range() {
-      lights[y][x] += 1;
-    }
+impl Monoid for Linear {
+  fn identity() -> Linear {
+    return Linear{mul: 1, add: 0};
+  }
+
+  fn then(self, next: Linear) -> Linear {
+    return Linear{mul: next.mul.saturating_mul(self.mul), add: next.mul.saturating_mul(self.add).saturating_add(next.add)};
   }
 }
 
-fn turn_down_light_brightness(lights: &mut Vec<Vec<u32>>, instruction: &Instruction) {
-  for y in instruction.get_y_range() {
-    for x in instruction.get_x_range() {
-      if lights[y][x] == 0 { continue }
-      lights[y][x] -= 1;
-    }
+/* A user-supplied linear map for each action, given as `--affine on=2,1` for b -> 2b + 1. */
+struct Affine {
+  on: Linear,
+  off: Linear,
+  toggle: Linear
+}
+
+impl Semantics for Affine {
+  type Effect = Linear;
+
+  fn effect(&self, action: Action) -> Linear {
+    return match action {
+      Action::TurnOn => self.on,
+      Action::TurnOff => self.off,
+      Action::Toggle => self.toggle
+    };
+  }
+
+  fn value(&self, effect: Linear) -> u128 {
+    return effect.add;
   }
 }
 
-fn toggle_light_brightness(lights: &mut Vec<Vec<u32>>, instruction: &Instruction) {
-  for y in instruction.get_y_range() {
-    for x in instruction.get_x_range() {
-      lights[y][x] += 2;
+fn parse_affine(specs: &[&str]) -> Affine {
+  let mut affine = Affine{on: Linear::identity(), off: Linear::identity(), toggle: Linear::identity()};
+  for spec in specs.iter() {
+    let (action, map) = spec.split_once('=').unwrap_or_else(|| panic!("Expected action=mul,add but got {}", spec));
+    let (mul, add) = map.split_once(',').unwrap_or_else(|| panic!("Expected action=mul,add but got {}", spec));
+    let linear = Linear{mul: mul.trim().parse::<u128>().unwrap(), add: add.trim().parse::<u128>().unwrap()};
+    match action.trim() {
+      "on" => affine.on = linear,
+      "off" => affine.off = linear,
+      "toggle" => affine.toggle = linear,
+      _ => panic!("Unknown action: {}", action)
     }
   }
+  return affine;
 }
 
-fn run_with_brightness_and_count_lights_on(instructions: &Vec<Instruction>) -> u32 {
-  let mut lights: Vec<Vec<u32>> = vec![vec![0; 1000]; 1000];
+/*
+  The instruction corners cut the plane into a grid of rectangles that no
+  instruction edge crosses, so every light in one rectangle ends up the same.
+  Only those rectangles are stored, at most (2n + 1)^2 for n instructions
+  however far the coordinates reach.
+*/
+fn total<S: Semantics>(instructions: &[Instruction], semantics: &S) -> u128 {
+  let cuts = |bounds: &dyn Fn(&Instruction) -> (u64, u64)| {
+    let mut cuts = instructions.iter().flat_map(|instruction| {
+      let (lower, upper) = bounds(instruction);
+      [lower as u128, upper as u128 + 1]
+    }).collect::<Vec<u128>>();
+    cuts.sort();
+    cuts.dedup();
+    cuts
+  };
+  let xs = cuts(&|instruction| (instruction.lower_x, instruction.upper_x));
+  let ys = cuts(&|instruction| (instruction.lower_y, instruction.upper_y));
+  if xs.len() < 2 || ys.len() < 2 { return 0; }
+  let width = xs.len() - 1;
+  let mut cells = vec![S::Effect::identity(); width * (ys.len() - 1)];
 
   for instruction in instructions.iter() {
-    match instruction.instruction.as_str() {
-      "turn on" => turn_up_light_brightness(&mut lights, &instruction),
-      "turn off" => turn_down_light_brightness(&mut lights, &instruction),
-      "toggle" => toggle_light_brightness(&mut lights, &instruction),
-      _ => ()
+    let effect = semantics.effect(instruction.action);
+    let column = |coordinate: u128| xs.binary_search(&coordinate).unwrap();
+    let row = |coordinate: u128| ys.binary_search(&coordinate).unwrap();
+    let (first_x, last_x) = (column(instruction.lower_x as u128), column(instruction.upper_x as u128 + 1));
+    for y in row(instruction.lower_y as u128)..row(instruction.upper_y as u128 + 1) {
+      for cell in cells[y * width + first_x..y * width + last_x].iter_mut() {
+        *cell = cell.then(effect);
+      }
     }
   }
 
-  return lights.iter().fold(0, |acc, c|
-    acc + c.iter().sum::<u32>());
+  return cells.iter().enumerate().map(|(index, cell)| {
+    let (x, y) = (index % width, index / width);
+    semantics.value(*cell).saturating_mul((xs[x + 1] - xs[x]) * (ys[y + 1] - ys[y]))
+  }).fold(0, |sum: u128, lights| sum.saturating_add(lights));
 }
 
 fn main() {
+  let args = Args::parse();
   let contents = fs::read_to_string("./data/q6.txt")
     .expect("Should have been able to read file");
 
-  let re 
+  let re
     = Regex::new(r"(turn on|turn off|toggle) ([\d]+),([\d]+) through ([\d]+),([\d]+)")
     .unwrap();
 
-  let instructions: Vec<Instruction> = contents.split("\n")
+  let instructions: Vec<Instruction> = contents.lines()
+    .filter(|line| !line.is_empty())
     .map(|instruction_str| {
-      let caps = re.captures(instruction_str).unwrap();
+      let caps = re.captures(instruction_str)
+        .unwrap_or_else(|| panic!("Was unable to match line: {}", instruction_str));
+      let (x1, y1, x2, y2) = (caps[2].parse::<u64>().unwrap(), caps[3].parse::<u64>().unwrap(),
+        caps[4].parse::<u64>().unwrap(), caps[5].parse::<u64>().unwrap());
       Instruction {
-        instruction: caps[1].to_string(),
-        lower_x: caps[2].parse::<usize>().unwrap(),
-        lower_y: caps[3].parse::<usize>().unwrap(),
-        upper_x: caps[4].parse::<usize>().unwrap(),
-        upper_y: caps[5].parse::<usize>().unwrap()
+        action: match &caps[1] {
+          "turn on" => Action::TurnOn,
+          "turn off" => Action::TurnOff,
+          _ => Action::Toggle
+        },
+        lower_x: x1.min(x2),
+        lower_y: y1.min(y2),
+        upper_x: x1.max(x2),
+        upper_y: y1.max(y2)
       }
     })
     .collect::<Vec<Instruction>>();
 
-  println!("Part 1: {}", run_and_count_lights_on(&instructions));
-  println!("Part 2: {}", run_with_brightness_and_count_lights_on(&instructions));
-}
\ No newline at end of file
+  println!("Part 1: {}", total(&instructions, &OnOff));
+  println!("Part 2: {}", total(&instructions, &Brightness));
+  let affine = args.values("--affine");
+  if !affine.is_empty() {
+    println!("Part 3: {}", total(&instructions, &parse_affine(&affine)));
+  }
+}
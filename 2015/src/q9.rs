@@ -1,75 +1,185 @@
 use std::fs;
 use regex::Regex;
-use itertools::Itertools;
-use std::collections::HashMap;
-use std::collections::HashSet;
-
-fn find_shortest_path_through_all_nodes(
-    nodes: &HashSet<String>, 
-    vertices: &HashMap<String, u32>
-) -> u32 
-{
-  let mut shortest_path: u32 = u32::MAX;
-
-  for perm in nodes.iter().permutations(nodes.len()) {
-    let mut curr_length: u32 = 0;
-
-    for i in 0..perm.len() - 1 {
-      let curr_vertex = format!("{},{}", perm[i], perm[i + 1]);
-      curr_length += vertices.get(&curr_vertex).unwrap();
-    }
+use aoc_common::answer::Answer;
+use aoc_common::cli::Args;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
-    if curr_length < shortest_path { shortest_path = curr_length }
-  }
+#[derive(Clone, Copy, PartialEq)]
+enum RouteKind {
+  Open,
+  Closed
+}
 
-  return shortest_path;
+#[derive(Clone, Copy, PartialEq)]
+enum Objective {
+  Shortest,
+  Longest
 }
 
-fn find_longest_path_through_all_nodes(
-  nodes: &HashSet<String>, 
-  vertices: &HashMap<String, u32>
-) -> u32 
-{
-let mut longest_path: u32 = u32::MIN;
+impl Objective {
+  fn is_better(&self, candidate: u32, current: Option<u32>) -> bool {
+    return match (self, current) {
+      (_, None) => true,
+      (Objective::Shortest, Some(current)) => candidate < current,
+      (Objective::Longest, Some(current)) => candidate > current
+    };
+  }
+}
 
-for perm in nodes.iter().permutations(nodes.len()) {
-  let mut curr_length: u32 = 0;
+struct Graph {
+  names: Vec<String>,
+  distances: Vec<Vec<Option<u32>>>
+}
+
+impl Graph {
+  fn new() -> Self {
+    return Graph{names: Vec::new(), distances: Vec::new()};
+  }
 
-  for i in 0..perm.len() - 1 {
-    let curr_vertex = format!("{},{}", perm[i], perm[i + 1]);
-    curr_length += vertices.get(&curr_vertex).unwrap();
+  fn index_of(&mut self, name: &str) -> usize {
+    if let Some(index) = self.names.iter().position(|n| n == name) { return index; }
+    self.names.push(name.to_string());
+    for row in self.distances.iter_mut() { row.push(None); }
+    self.distances.push(vec![None; self.names.len()]);
+    return self.names.len() - 1;
   }
 
-  if curr_length > longest_path { longest_path = curr_length }
+  fn add_edge(&mut self, from: &str, to: &str, distance: u32) {
+    let from = self.index_of(from);
+    let to = self.index_of(to);
+    self.distances[from][to] = Some(distance);
+    self.distances[to][from] = Some(distance);
+  }
 }
 
-return longest_path;
+struct Route {
+  length: u32,
+  cities: Vec<String>
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q9.txt")
-    .expect("Should have been able to read file");
+/*
+  Held-Karp: best[mask][last] is the best length of a path that visits exactly
+  the cities in `mask` and ends at `last`, built up one city at a time. A
+  closed tour is an open path from a fixed start plus the edge back to it.
+*/
+fn find_route(graph: &Graph, kind: RouteKind, start: Option<usize>, objective: Objective) -> Option<Route> {
+  let n = graph.names.len();
+  if n == 0 { return None; }
+  let start = if kind == RouteKind::Closed { Some(start.unwrap_or(0)) } else { start };
+  let full = (1_usize << n) - 1;
+  let mut best: Vec<Option<u32>> = vec![None; (1 << n) * n];
+  let mut parent: Vec<usize> = vec![usize::MAX; (1 << n) * n];
+
+  for city in 0..n {
+    if start.is_none() || start == Some(city) { best[(1 << city) * n + city] = Some(0); }
+  }
+
+  for mask in 1..=full {
+    for last in 0..n {
+      let length = match best[mask * n + last] {
+        Some(length) => length,
+        None => continue
+      };
+      for next in 0..n {
+        if mask & (1 << next) != 0 { continue; }
+        let distance = match graph.distances[last][next] {
+          Some(distance) => distance,
+          None => continue
+        };
+        let next_index = (mask | (1 << next)) * n + next;
+        if objective.is_better(length + distance, best[next_index]) {
+          best[next_index] = Some(length + distance);
+          parent[next_index] = last;
+        }
+      }
+    }
+  }
+
+  let mut best_length: Option<u32> = None;
+  let mut best_last = 0;
+  for last in 0..n {
+    let closing = match kind {
+      RouteKind::Open => Some(0),
+      RouteKind::Closed if n == 1 => Some(0),
+      RouteKind::Closed => graph.distances[last][start.unwrap()]
+    };
+    if let (Some(length), Some(closing)) = (best[full * n + last], closing) {
+      if objective.is_better(length + closing, best_length) {
+        best_length = Some(length + closing);
+        best_last = last;
+      }
+    }
+  }
 
-  let mut vertices: HashMap<String, u32> = HashMap::new();
-  let mut nodes: HashSet<String> = HashSet::new();
+  let length = best_length?;
+  let mut order = Vec::new();
+  let mut mask = full;
+  let mut city = best_last;
+  while city != usize::MAX {
+    order.push(city);
+    let prev = parent[mask * n + city];
+    mask ^= 1 << city;
+    city = prev;
+  }
+  order.reverse();
+  if kind == RouteKind::Closed && n > 1 { order.push(order[0]); }
+
+  return Some(Route{length, cities: order.iter().map(|city| graph.names[*city].clone()).collect()});
+}
+
+fn parse_graph(contents: &str) -> Graph {
+  let mut graph = Graph::new();
   let re = Regex::new(r"([A-Za-z]+) to ([A-Za-z]+) = ([\d]+)").unwrap();
 
-  for vertex in contents.split("\n") {
-    let caps = re.captures(vertex);
-    match caps {
-      Some(cap) => {
-        let route = format!("{},{}", cap[1].to_string(), cap[2].to_string());
-        let backwards_route = format!("{},{}", cap[2].to_string(), cap[1].to_string());
-        let distance = cap[3].parse::<u32>().unwrap();
-        vertices.insert(route, distance);
-        vertices.insert(backwards_route, distance);
-        nodes.insert(cap[1].to_string());
-        nodes.insert(cap[2].to_string());
-      },
-      None => println!("Was unable to match line: {}", vertex)
+  for vertex in contents.lines().filter(|line| !line.is_empty()) {
+    match re.captures(vertex) {
+      Some(cap) => graph.add_edge(&cap[1], &cap[2], cap[3].parse::<u32>().unwrap()),
+      None => panic!("Was unable to match line: {}", vertex)
     }
   }
+  return graph;
+}
+
+fn find_start(graph: &Graph, start: Option<&str>) -> Option<usize> {
+  return start.map(|name| graph.names.iter().position(|n| n == name)
+    .unwrap_or_else(|| panic!("Unknown city: {}", name)));
+}
+
+fn route_answer(route: Option<Route>) -> Answer {
+  return match route {
+    Some(route) => route.length.into(),
+    None => "no route visits every city".into()
+  };
+}
 
-  println!("Part 1: {}", find_shortest_path_through_all_nodes(&nodes, &vertices));
-  println!("Part 2: {}", find_longest_path_through_all_nodes(&nodes, &vertices));
-}
\ No newline at end of file
+fn solve(contents: &str, _ctx: &Context, kind: RouteKind, start: Option<&str>) -> Result<Vec<Answer>, Cancelled> {
+  let graph = parse_graph(contents);
+  let start = find_start(&graph, start);
+  return Ok(vec![
+    route_answer(find_route(&graph, kind, start, Objective::Shortest)),
+    route_answer(find_route(&graph, kind, start, Objective::Longest))
+  ]);
+}
+
+fn main() {
+  let args = Args::parse();
+  let kind = if args.flag("--closed") { RouteKind::Closed } else { RouteKind::Open };
+  let start = args.value("--start");
+
+  if args.flag("--route") {
+    let contents = fs::read_to_string("./data/q9.txt")
+      .expect("Should have been able to read file");
+    let graph = parse_graph(&contents);
+    let start = find_start(&graph, start);
+    for (part, objective) in [(1, Objective::Shortest), (2, Objective::Longest)] {
+      match find_route(&graph, kind, start, objective) {
+        Some(route) => println!("Part {}: {}\n  {}", part, route.length, route.cities.join(" -> ")),
+        None => println!("Part {}: no route visits every city", part)
+      }
+    }
+    return;
+  }
+
+  runner::run("q9", |contents, ctx| solve(contents, ctx, kind, start));
+}
@@ -1,5 +1,7 @@
-use std::fs;
 use regex::Regex;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 struct Fold {
   axis: char,
@@ -67,28 +69,17 @@ fn count_stars_after_first_fold(grid: &Vec<Vec<bool>>, folds: &Vec<Fold>) -> u32
     acc + row.iter().fold(0, |acc, col| acc + *col as u32));
 }
 
-fn get_code_after_all_folds(grid: &Vec<Vec<bool>>, folds: &Vec<Fold>) -> String {
-  let mut curr_grid = grid.clone();
+fn get_code_after_all_folds(grid: &[Vec<bool>], folds: &[Fold]) -> Answer {
+  let mut curr_grid = grid.to_vec();
 
   for fold in folds.iter() {
-    curr_grid = simulate_fold(&curr_grid, &fold);
-  }
-
-  let mut res = String::new();
-
-  for row in curr_grid.iter() {
-    let row_str: String = row.iter()
-      .map(|m| if *m {'#'} else {'.'}).collect();
-    res.insert(res.len(), '\n');
-    res.insert_str(res.len(), row_str.as_str());
+    curr_grid = simulate_fold(&curr_grid, fold);
   }
 
-  return res;
+  return Answer::from_pixels(&curr_grid);
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q13.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
 
   let mut points: Vec<(usize, usize)> = Vec::new();
   let mut folds: Vec<Fold> = Vec::new();
@@ -129,6 +120,12 @@ fn main() {
     grid[*y][*x] = true;
   }
 
-  println!("Part 1: {}", count_stars_after_first_fold(&grid, &folds));
-  println!("Part 2: {}", get_code_after_all_folds(&grid, &folds));
+  return Ok(vec![
+    count_stars_after_first_fold(&grid, &folds).into(),
+    get_code_after_all_folds(&grid, &folds)
+  ]);
+}
+
+fn main() {
+  runner::run("q13", solve);
 }
\ No newline at end of file
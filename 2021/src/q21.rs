@@ -2,6 +2,9 @@ use std::collections::HashMap;
 use std::cmp::max;
 use std::fs;
 use regex::Regex;
+use aoc_common::cli::Args;
+use aoc_common::trace::Tracer;
+use aoc_common::{trace_leaf, trace_memo_hit, trace_node};
 
 #[derive(Clone)]
 struct Player {
@@ -76,7 +79,7 @@ fn get_final_score(pos1: u32, pos2: u32) -> u32 {
 }
 
 fn calculate_wins(player1: &Player, player2: &Player, is_player1: bool, 
-  memo: &mut HashMap<String, (u64, u64)>, roll_freq: &HashMap<u32, u64>
+  memo: &mut HashMap<String, (u64, u64)>, roll_freq: &HashMap<u32, u64>, tracer: &Tracer, depth: usize
 ) -> (u64, u64) {
   let mut p1_points = player1.get_points();
   if p1_points > 21 { p1_points = 21; }
@@ -86,20 +89,26 @@ fn calculate_wins(player1: &Player, player2: &Player, is_player1: bool,
   let key = format!("{}-{}-{}-{}-{}", player1.get_pos(), p1_points, 
     player2.get_pos(), p2_points, is_player1);
 
-  if memo.contains_key(&key) { return *memo.get(&key).unwrap()}
+  if memo.contains_key(&key) {
+    trace_memo_hit!(tracer, depth, "{} -> {:?}", key, memo.get(&key).unwrap());
+    return *memo.get(&key).unwrap()
+  }
 
   if p1_points == 21 {
+    trace_leaf!(tracer, depth, "{} player 1 wins", key);
     let result = (1, 0);
     memo.insert(key, result);
     return result;
   }
 
   if p2_points == 21 {
+    trace_leaf!(tracer, depth, "{} player 2 wins", key);
     let result = (0, 1);
     memo.insert(key, result);
     return result;
   }
 
+  trace_node!(tracer, depth, "{}", key);
   let next_is_player1 = !is_player1;
   let mut wins = (0, 0);
 
@@ -109,7 +118,7 @@ fn calculate_wins(player1: &Player, player2: &Player, is_player1: bool,
       let mut next_player1 = player1.clone();
       next_player1.move_spaces(i);
       let next_wins = calculate_wins(&next_player1, player2, next_is_player1, 
-        memo, roll_freq);
+        memo, roll_freq, tracer, depth + 1);
       wins = (wins.0 + (next_wins.0 * freq), wins.1 + (next_wins.1 * freq));
     }
   } else {
@@ -118,7 +127,7 @@ fn calculate_wins(player1: &Player, player2: &Player, is_player1: bool,
       let mut next_player2 = player2.clone();
       next_player2.move_spaces(i);
       let next_wins = calculate_wins(player1, &next_player2, next_is_player1, 
-        memo, roll_freq);
+        memo, roll_freq, tracer, depth + 1);
       wins = (wins.0 + (next_wins.0 * freq), wins.1 + (next_wins.1 * freq));
     }
   }
@@ -137,17 +146,19 @@ fn calculate_wins(player1: &Player, player2: &Player, is_player1: bool,
   9: 3 3 3
 */
 
-fn get_win_difference(pos1: u32, pos2: u32) -> u64 {
+fn get_win_difference(pos1: u32, pos2: u32, tracer: &Tracer) -> u64 {
   let mut memo: HashMap<String, (u64, u64)> = HashMap::new();
   let roll_frequencies: HashMap<u32, u64> = HashMap::from_iter(vec![
     (3, 1), (4, 3), (5, 6), (6, 7), (7, 6), (8, 3), (9, 1)
   ]);
   let (p1_win, p2_win) = calculate_wins(&Player::new(pos1), &Player::new(pos2), 
-    true, &mut memo, &roll_frequencies);
+    true, &mut memo, &roll_frequencies, tracer, 0);
+  tracer.summary("dirac dice");
   return max(p1_win, p2_win);
 }
 
 fn main() {
+  let tracer = Tracer::from_args(&Args::parse());
   let contents = fs::read_to_string("./data/q21.txt")
     .expect("Should have been able to read file");
 
@@ -169,5 +180,5 @@ fn main() {
   }
 
   println!("Part 1: {}", get_final_score(pos1, pos2));
-  println!("Part 2: {}", get_win_difference(pos1, pos2));
+  println!("Part 2: {}", get_win_difference(pos1, pos2, &tracer));
 }
\ No newline at end of file
@@ -1,8 +1,134 @@
 use std::fs;
+use aoc_common::vm::{self, Flow, Machine, Reg, State, Word};
+
+const REGISTERS: &[&str; 4] = &["w", "x", "y", "z"];
+const Z: Reg = Reg(3);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+  Register(Reg),
+  Immediate(Word)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instruction {
+  Inp(Reg),
+  Add(Reg, Operand),
+  Mul(Reg, Operand),
+  Div(Reg, Operand),
+  Mod(Reg, Operand),
+  Eql(Reg, Operand)
+}
+
+impl vm::Instruction for Instruction {
+  fn execute(&self, state: &mut State) -> Flow {
+    let value = |state: &State, operand: &Operand| match operand {
+      Operand::Register(reg) => state.get(*reg),
+      Operand::Immediate(val) => *val
+    };
+
+    match self {
+      Instruction::Inp(reg) => match state.read_input() {
+        Some(val) => state.set(*reg, val),
+        None => return Flow::Halt
+      },
+      Instruction::Add(reg, operand) => state.set(*reg, state.get(*reg) + value(state, operand)),
+      Instruction::Mul(reg, operand) => state.set(*reg, state.get(*reg) * value(state, operand)),
+      Instruction::Div(reg, operand) => state.set(*reg, state.get(*reg) / value(state, operand)),
+      Instruction::Mod(reg, operand) => state.set(*reg, state.get(*reg) % value(state, operand)),
+      Instruction::Eql(reg, operand) => state.set(*reg, (state.get(*reg) == value(state, operand)) as Word)
+    }
+    return Flow::Next;
+  }
+}
+
+fn parse_register(name: &str) -> Reg {
+  return Reg(REGISTERS.iter().position(|reg| *reg == name)
+    .unwrap_or_else(|| panic!("Unknown register: {}", name)));
+}
+
+fn parse_operand(operand: &str) -> Operand {
+  return match operand.parse::<Word>() {
+    Ok(val) => Operand::Immediate(val),
+    Err(_) => Operand::Register(parse_register(operand))
+  };
+}
+
+fn parse_instruction(line: &str) -> Instruction {
+  let parts = line.split(' ').collect::<Vec<&str>>();
+  let reg = parse_register(parts[1]);
+  return match parts[0] {
+    "inp" => Instruction::Inp(reg),
+    "add" => Instruction::Add(reg, parse_operand(parts[2])),
+    "mul" => Instruction::Mul(reg, parse_operand(parts[2])),
+    "div" => Instruction::Div(reg, parse_operand(parts[2])),
+    "mod" => Instruction::Mod(reg, parse_operand(parts[2])),
+    "eql" => Instruction::Eql(reg, parse_operand(parts[2])),
+    _ => panic!("Was unable to match line: {}", line)
+  };
+}
+
+fn accepts(program: &[Instruction], digits: &[Word]) -> bool {
+  let mut alu = Machine::new(program, REGISTERS.len()).with_input(digits.iter().copied());
+  alu.run();
+  return alu.state.input.is_empty() && alu.state.get(Z) == 0;
+}
+
+/*
+  MONAD is 14 copies of the same block, one per digit. Each block either pushes
+  `digit + offset` onto a base-26 stack held in z (`div z 1`) or pops it again
+  (`div z 26`), and the pop only leaves z unchanged when `digit == popped +
+  check`. Pairing pushes with pops gives one equality constraint per pair.
+*/
+fn get_digit_constraints(program: &[Instruction]) -> Vec<(usize, usize, Word)> {
+  let blocks = program.split(|instr| matches!(instr, Instruction::Inp(_)))
+    .skip(1)
+    .collect::<Vec<&[Instruction]>>();
+  let mut stack: Vec<(usize, Word)> = Vec::new();
+  let mut constraints = Vec::new();
+
+  for (digit, block) in blocks.iter().enumerate() {
+    let pops = block.contains(&Instruction::Div(Z, Operand::Immediate(26)));
+    let check = block.iter().find_map(|instr| match instr {
+      Instruction::Add(Reg(1), Operand::Immediate(val)) => Some(*val),
+      _ => None
+    }).expect("Block should compare x against an immediate");
+    let offset = block.iter().rev().find_map(|instr| match instr {
+      Instruction::Add(Reg(2), Operand::Immediate(val)) => Some(*val),
+      _ => None
+    }).expect("Block should add an immediate offset to y");
+
+    if pops {
+      let (pushed_digit, pushed_offset) = stack.pop().expect("Block should have a digit to pop");
+      constraints.push((pushed_digit, digit, pushed_offset + check));
+    } else {
+      stack.push((digit, offset));
+    }
+  }
+
+  return constraints;
+}
+
+fn find_model_number(program: &[Instruction], largest: bool) -> u64 {
+  let constraints = get_digit_constraints(program);
+  let mut digits: Vec<Word> = vec![0; constraints.len() * 2];
+
+  for (first, second, delta) in constraints.iter() {
+    if delta.abs() > 8 { panic!("Digits {} and {} can never differ by {}", first, second, delta); }
+    let first_digit = if largest { 9.min(9 - delta) } else { 1.max(1 - delta) };
+    digits[*first] = first_digit;
+    digits[*second] = first_digit + delta;
+  }
+
+  if !accepts(program, &digits) { panic!("MONAD rejected {:?}", digits); }
+  return digits.iter().fold(0, |acc, digit| acc * 10 + *digit as u64);
+}
 
 fn main() {
-  let _contents = fs::read_to_string("./data/q_.txt")
+  let contents = fs::read_to_string("./data/q24.txt")
     .expect("Should have been able to read file");
-  println!("Part 1: {}", 0);
-  println!("Part 2: {}", 0);
-}
\ No newline at end of file
+  let program = contents.split('\n').map(parse_instruction).collect::<Vec<Instruction>>();
+
+  println!("Part 1: {}", find_model_number(&program, true));
+  println!("Part 2: {}", find_model_number(&program, false));
+}
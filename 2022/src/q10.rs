@@ -1,61 +1,42 @@
-use std::fs;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
+use aoc_common::vm::{self, Flow, Hook, Machine, Reg, State};
 
-const IMPORTANT_CYCLES: &[i32; 6] = &[20, 60, 100, 140, 180, 220];
+const IMPORTANT_CYCLES: &[i64; 6] = &[20, 60, 100, 140, 180, 220];
 const SCREEN_WIDTH: usize = 40;
+const X: Reg = Reg(0);
 
 enum Instruction {
   Noop,
-  Addx(i32)
+  Addx(i64)
 }
 
-enum State {
-  Free,
-  MidAdd,
+impl vm::Instruction for Instruction {
+  fn cycles(&self) -> u64 {
+    return match self {
+      Instruction::Noop => 1,
+      Instruction::Addx(_) => 2
+    };
+  }
+
+  fn execute(&self, state: &mut State) -> Flow {
+    if let Instruction::Addx(val) = self { state.set(X, state.get(X) + val); }
+    return Flow::Next;
+  }
 }
 
-struct CPU {
-  reg: i32,
-  cycle: i32,
-  state: State,
-  signal_strengths: Vec<i32>,
+struct Crt {
+  signal_strengths: Vec<i64>,
   pixel_data: Vec<char>
 }
- 
-impl CPU {
-  fn new() -> Self {
-    return CPU{reg: 1, cycle: 0, state: State::Free, signal_strengths: Vec::new(), pixel_data: Vec::new()};
-  }
-
-  fn simulate(&mut self, instructions: &Vec<Instruction>) {
-    let mut index = 0;
-    while index < instructions.len() {
-      let sprite_pos = self.cycle % SCREEN_WIDTH as i32;
-      if sprite_pos >= self.reg - 1 && sprite_pos <= self.reg + 1 {
-        self.pixel_data.push('#');
-      } else {
-        self.pixel_data.push('.');
-      }
 
-      self.cycle += 1;
-      if IMPORTANT_CYCLES.contains(&self.cycle) { self.signal_strengths.push(self.cycle * self.reg); }
-
-      match instructions[index] {
-        Instruction::Noop => index += 1,
-        Instruction::Addx(val) => {
-          match self.state {
-            State::Free => self.state = State::MidAdd,
-            State::MidAdd => {
-              self.state = State::Free;
-              self.reg += val;
-              index += 1;
-            }
-          }
-        }
-      }
-    } 
+impl Crt {
+  fn new() -> Self {
+    return Crt{signal_strengths: Vec::new(), pixel_data: Vec::new()};
   }
 
-  fn get_signal_strengths(&self) -> &Vec<i32> {
+  fn get_signal_strengths(&self) -> &Vec<i64> {
     return &self.signal_strengths;
   }
 
@@ -64,32 +45,46 @@ impl CPU {
   }
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q10.txt")
-    .expect("Should have been able to read file");
+impl Hook<Instruction> for Crt {
+  fn on_cycle(&mut self, state: &State) {
+    let cycle = state.cycle as i64;
+    let reg = state.get(X);
+    let sprite_pos = (cycle - 1) % SCREEN_WIDTH as i64;
+    if sprite_pos >= reg - 1 && sprite_pos <= reg + 1 {
+      self.pixel_data.push('#');
+    } else {
+      self.pixel_data.push('.');
+    }
+
+    if IMPORTANT_CYCLES.contains(&cycle) { self.signal_strengths.push(cycle * reg); }
+  }
+}
+
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
   let lines = contents.split('\n').collect::<Vec<&str>>();
   let instructions = lines.iter().map(|line| {
     if *line == "noop" {
       Instruction::Noop
     } else {
       let parts = line.split(' ').collect::<Vec<&str>>();
-      Instruction::Addx(parts[1].parse::<i32>().unwrap())
+      Instruction::Addx(parts[1].parse::<i64>().unwrap())
     }
   }).collect::<Vec<Instruction>>();
 
-  let mut cpu = CPU::new();
-  cpu.simulate(&instructions);
-  
-  let total_signal_strengths: i32 = cpu.get_signal_strengths().iter().sum();
-  let pixel_data = cpu.get_pixel_data();
+  let mut cpu = Machine::with_registers(&instructions, vec![1]);
+  let mut crt = Crt::new();
+  cpu.run_with(&mut crt);
+
+  let total_signal_strengths: i64 = crt.get_signal_strengths().iter().sum();
+  let pixel_data = crt.get_pixel_data();
   let mut rows = Vec::new();
   for i in (0..pixel_data.len()).step_by(SCREEN_WIDTH) {
     rows.push(pixel_data[i..i + SCREEN_WIDTH].iter().collect::<String>());
   }
 
-  println!("Part 1: {}", total_signal_strengths);
-  println!("Part 2:");
-  for row in rows.iter() {
-    println!("{}", row);
-  }
-}
\ No newline at end of file
+  return Ok(vec![total_signal_strengths.into(), Answer::glyphs(rows)]);
+}
+
+fn main() {
+  runner::run("q10", solve);
+}
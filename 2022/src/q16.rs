@@ -1,5 +1,8 @@
-use std::{cmp::max, collections::{HashMap, HashSet, VecDeque}, fs};
+use std::{cmp::max, collections::{HashMap, HashSet, VecDeque}};
 use regex::Regex;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 #[derive(Debug)]
 struct Valve {
@@ -72,25 +75,27 @@ fn get_opened_valves(openable_valves: &Vec<String>, mut bitmap: u32, use_ones: b
   return opened_valves;
 }
 
-fn get_max_flow_pair(valves: &HashMap<String, Valve>, adj_list: &HashMap<String, Vec<(String, u32)>>) -> u32 {
+fn get_max_flow_pair(valves: &HashMap<String, Valve>, adj_list: &HashMap<String, Vec<(String, u32)>>,
+  ctx: &Context) -> Result<u32, Cancelled>
+{
   let mut openable_valves = adj_list.keys().map(|k| k.clone()).collect::<Vec<String>>();
   openable_valves.remove(openable_valves.iter().position(|v| *v == "AA".to_string()).unwrap());
   let number_of_valves = openable_valves.len() as u32;
 
   let mut max_flow = 0;
-  for i in 0..2_u32.pow(number_of_valves) / 2 {
+  let number_of_splits = 2_u32.pow(number_of_valves) / 2;
+  for i in 0..number_of_splits {
+    ctx.progress("valve splits", i as u64, Some(number_of_splits as u64))?;
     let opened_valves = get_opened_valves(&openable_valves, i, true);
     let other_opened_valves = get_opened_valves(&openable_valves, i, false);
     max_flow = max(max_flow, get_max_flow(valves, adj_list, String::from("AA"), 26, 0, opened_valves) +
       get_max_flow(valves, adj_list, String::from("AA"), 26, 0, other_opened_valves));
   }
 
-  return max_flow;
+  return Ok(max_flow);
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q16.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
   let re = Regex::new(r"Valve ([A-Z]+) has flow rate=([\d]+); tunnel[s]? lead[s]? to valve[s]? (.*)")
     .unwrap();
 
@@ -112,6 +117,12 @@ fn main() {
   ).collect::<HashMap<String, Vec<(String, u32)>>>();
   adj_list.insert(String::from("AA"), get_shortest_path_lens(&String::from("AA"), &valves));
 
-  println!("Part 1: {}", get_max_flow(&valves, &adj_list, String::from("AA"), 30, 0, HashSet::new()));
-  println!("Part 2: {}", get_max_flow_pair(&valves, &adj_list));
+  return Ok(vec![
+    get_max_flow(&valves, &adj_list, String::from("AA"), 30, 0, HashSet::new()).into(),
+    get_max_flow_pair(&valves, &adj_list, ctx)?.into()
+  ]);
+}
+
+fn main() {
+  runner::run("q16", solve);
 }
\ No newline at end of file
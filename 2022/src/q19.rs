@@ -1,5 +1,8 @@
 use std::{fs, collections::HashMap, cmp::max};
 use regex::Regex;
+use aoc_common::cli::Args;
+use aoc_common::trace::Tracer;
+use aoc_common::{trace_leaf, trace_memo_hit, trace_node};
 
 #[derive(Debug)]
 struct Blueprint {
@@ -19,20 +22,30 @@ impl Blueprint {
     return Blueprint {id, ore_cost, clay_cost, ob_ore_cost, ob_clay_cost,
       geo_ore_cost, geo_ob_cost};
   }
+
+  /* Spending is capped at one robot a minute, so more robots of a kind than its largest cost never help. */
+  fn max_robots(&self) -> [u32; 3] {
+    let max_ore = max(max(max(self.ore_cost, self.clay_cost), self.ob_ore_cost), self.geo_ore_cost);
+    return [max_ore, self.ob_clay_cost, self.geo_ob_cost];
+  }
 }
 
-fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>, 
+#[allow(clippy::too_many_arguments)]
+fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>, tracer: &Tracer, depth: usize,
   max_robots: &[u32; 3], time: i32, robots: [u32; 4], resources: [u32; 4]) -> u32
 {
-  if time == 0 { return resources[3]; }
+  if time == 0 {
+    trace_leaf!(tracer, depth, "out of time with {} geodes", resources[3]);
+    return resources[3];
+  }
 
   let hash = format!("{},{},{},{},{},{},{},{},{}", time, robots[0], robots[1],
     robots[2], robots[3], resources[0], resources[1], resources[2], resources[3]);
-  let in_memo = memo.get(&hash);
-  match in_memo {
-    Some(max_geodes) => return *max_geodes,
-    _ => ()
+  if let Some(max_geodes) = memo.get(&hash) {
+    trace_memo_hit!(tracer, depth, "{} -> {}", hash, max_geodes);
+    return *max_geodes
   }
+  trace_node!(tracer, depth, "time={} robots={:?} resources={:?}", time, robots, resources);
 
   let mut max_geodes = resources[3] + (robots[3] * time as u32);
 
@@ -54,7 +67,7 @@ fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>,
         next_resources[i] += robots[i];
       }
       next_time -= 1;
-      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, max_robots,
+      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, tracer, depth + 1, max_robots,
         next_time, next_robots, next_resources))
     }
   }
@@ -77,7 +90,7 @@ fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>,
         next_resources[i] += robots[i];
       }
       next_time -= 1;
-      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, max_robots,
+      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, tracer, depth + 1, max_robots,
         next_time, next_robots, next_resources))
     }
   }
@@ -103,7 +116,7 @@ fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>,
       for i in 0..next_resources.len() {
         next_resources[i] += robots[i];
       }
-      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, max_robots,
+      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, tracer, depth + 1, max_robots,
         next_time, next_robots, next_resources))
     }
   }
@@ -129,7 +142,7 @@ fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>,
       for i in 0..next_resources.len() {
         next_resources[i] += robots[i];
       }
-      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, max_robots,
+      max_geodes = max(max_geodes, get_max_geodes(blueprint, memo, tracer, depth + 1, max_robots,
         next_time,next_robots, next_resources))
     }
   }
@@ -138,31 +151,26 @@ fn get_max_geodes(blueprint: &Blueprint, memo: &mut HashMap<String, u32>,
   return max_geodes;
 }
 
-fn sum_quality_level(blueprints: &Vec<Blueprint>) -> u32 {
+fn sum_quality_level(blueprints: &[Blueprint], tracer: &Tracer) -> u32 {
   return blueprints.iter().map(|blueprint| {
-    let max_ore = max(max(max(blueprint.ore_cost, blueprint.clay_cost),
-      blueprint.ob_ore_cost), blueprint.geo_ore_cost);
-    let max_clay = blueprint.ob_clay_cost;
-    let max_ob = blueprint.geo_ob_cost;
-    let max_robots: [u32; 3] = [max_ore, max_clay, max_ob];
-    blueprint.id * get_max_geodes(blueprint, &mut HashMap::new(), &max_robots, 
-      24, [1, 0, 0, 0], [0, 0 ,0 ,0])
+    let max_geodes = get_max_geodes(blueprint, &mut HashMap::new(), tracer, 0, &blueprint.max_robots(),
+      24, [1, 0, 0, 0], [0, 0 ,0 ,0]);
+    tracer.summary(&format!("blueprint {}", blueprint.id));
+    blueprint.id * max_geodes
   }).sum();
 }
 
-fn mul_three_geodes(blueprints: &Vec<Blueprint>) -> u32 {
+fn mul_three_geodes(blueprints: &[Blueprint], tracer: &Tracer) -> u32 {
   return blueprints.iter().take(3).fold(1, |acc, blueprint| {
-    let max_ore = max(max(max(blueprint.ore_cost, blueprint.clay_cost),
-      blueprint.ob_ore_cost), blueprint.geo_ore_cost);
-    let max_clay = blueprint.ob_clay_cost;
-    let max_ob = blueprint.geo_ob_cost;
-    let max_robots: [u32; 3] = [max_ore, max_clay, max_ob];
-    acc * get_max_geodes(blueprint, &mut HashMap::new(), &max_robots, 
-      32, [1, 0, 0, 0], [0, 0 ,0 ,0])
+    let max_geodes = get_max_geodes(blueprint, &mut HashMap::new(), tracer, 0, &blueprint.max_robots(),
+      32, [1, 0, 0, 0], [0, 0 ,0 ,0]);
+    tracer.summary(&format!("blueprint {}", blueprint.id));
+    acc * max_geodes
   });
 }
 
 fn main() {
+  let tracer = Tracer::from_args(&Args::parse());
   let contents = fs::read_to_string("./data/q19.txt")
     .expect("Should have been able to read file");
   let re = Regex::new("Blueprint ([\\d]+): Each ore robot costs ([\\d]+) ore. \
@@ -177,6 +185,6 @@ fn main() {
     Blueprint::new(caps[0], caps[1], caps[2], caps[3], caps[4], caps[5], caps[6])
   }).collect::<Vec<Blueprint>>();
 
-  println!("Part 1: {}", sum_quality_level(&blueprints));
-  println!("Part 2: {}", mul_three_geodes(&blueprints));
+  println!("Part 1: {}", sum_quality_level(&blueprints, &tracer));
+  println!("Part 2: {}", mul_three_geodes(&blueprints, &tracer));
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
-use std::fs;
 use regex::Regex;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 const DUMMY: usize = 1;
 const CRATE_SIZE: usize = 4;
@@ -61,9 +63,7 @@ fn simulate_9001(mut crates: Vec<Vec<char>>, moves: &Vec<Move>) -> String {
   return tops;
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q5.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
   let lines = contents.split('\n').collect::<Vec<&str>>();
 
   let mut empty_index = 0;
@@ -87,6 +87,9 @@ fn main() {
       dst: caps[3].to_string().parse::<usize>().unwrap()
     };
   }).collect::<Vec<Move>>();
-  println!("Part 1: {}", simulate_9000(crates.clone(), &moves));
-  println!("Part 2: {}", simulate_9001(crates.clone(), &moves));
+  return Ok(vec![simulate_9000(crates.clone(), &moves).into(), simulate_9001(crates.clone(), &moves).into()]);
+}
+
+fn main() {
+  runner::run("q5", solve);
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
 use std::fs;
 use std::collections::HashMap;
 use regex::Regex;
+use aoc_common::cli::Args;
+use aoc_common::trace::Tracer;
+use aoc_common::{trace_leaf, trace_node, trace_prune};
 
 #[derive(Debug)]
 enum Compare {
@@ -72,11 +75,18 @@ fn sum_accepted_parts(rules: &HashMap<String, Vec<Rule>>, parts: &Vec<HashMap<St
   return total;
 }
 
-fn get_combo_ranges(rules: &HashMap<String, Vec<Rule>>, mut state: HashMap<String, (u64, u64)>, label: String)
-  -> Vec<HashMap<String, (u64, u64)>> 
+fn get_combo_ranges(rules: &HashMap<String, Vec<Rule>>, mut state: HashMap<String, (u64, u64)>, label: String,
+  tracer: &Tracer, depth: usize) -> Vec<HashMap<String, (u64, u64)>> 
 {
-  if label == "A" { return vec![state]; }
-  if label == "R" { return vec![]; }
+  if label == "A" {
+    trace_leaf!(tracer, depth, "accept {:?}", state);
+    return vec![state];
+  }
+  if label == "R" {
+    trace_prune!(tracer, depth, "reject {:?}", state);
+    return vec![];
+  }
+  trace_node!(tracer, depth, "workflow {} with {:?}", label, state);
 
   let curr_rules = rules.get(&label).unwrap();
   let mut ranges = Vec::new();
@@ -88,20 +98,20 @@ fn get_combo_ranges(rules: &HashMap<String, Vec<Rule>>, mut state: HashMap<Strin
         match comp {
           Compare::LessThan => {
             next_state.get_mut(part_name).unwrap().1 = *comp_val;
-            let curr_ranges = get_combo_ranges(rules, next_state.clone(), out_label.clone());
+            let curr_ranges = get_combo_ranges(rules, next_state.clone(), out_label.clone(), tracer, depth + 1);
             ranges.extend(curr_ranges);
             state.get_mut(part_name).unwrap().0 = *comp_val;
           },
           Compare::GreaterThan => {
             next_state.get_mut(part_name).unwrap().0 = *comp_val + 1;
-            let curr_ranges = get_combo_ranges(rules, next_state.clone(), out_label.clone());
+            let curr_ranges = get_combo_ranges(rules, next_state.clone(), out_label.clone(), tracer, depth + 1);
             ranges.extend(curr_ranges);
             state.get_mut(part_name).unwrap().1 = *comp_val + 1;
           }
         }
       },
       Rule::Label(out_label) => {
-        let curr_ranges = get_combo_ranges(rules, state.clone(), out_label.clone());
+        let curr_ranges = get_combo_ranges(rules, state.clone(), out_label.clone(), tracer, depth + 1);
         ranges.extend(curr_ranges);
       }
     }
@@ -110,14 +120,15 @@ fn get_combo_ranges(rules: &HashMap<String, Vec<Rule>>, mut state: HashMap<Strin
   return ranges;
 }
 
-fn count_accepted_combinations(rules: &HashMap<String, Vec<Rule>>) -> u64 {
+fn count_accepted_combinations(rules: &HashMap<String, Vec<Rule>>, tracer: &Tracer) -> u64 {
   let initial_state = HashMap::from([
     ("x".to_string(), (1, 4001)),
     ("m".to_string(), (1, 4001)),
     ("a".to_string(), (1, 4001)),
     ("s".to_string(), (1, 4001)),
   ]);
-  let combo_ranges = get_combo_ranges(rules, initial_state, "in".to_string());
+  let combo_ranges = get_combo_ranges(rules, initial_state, "in".to_string(), tracer, 0);
+  tracer.summary("workflows");
   return combo_ranges.iter().map(|ranges_map| {
     let ranges = ranges_map.values().collect::<Vec<&(u64, u64)>>();
     return ranges.iter().fold(1, |acc, (l, u)| acc * (u - l));
@@ -125,6 +136,7 @@ fn count_accepted_combinations(rules: &HashMap<String, Vec<Rule>>) -> u64 {
 }
 
 fn main() {
+  let tracer = Tracer::from_args(&Args::parse());
   let contents = fs::read_to_string("./data/q19.txt")
     .expect("Should have been able to read file");
   let lines = contents.split('\n').collect::<Vec<&str>>();
@@ -160,5 +172,5 @@ fn main() {
   }
 
   println!("Part 1: {}", sum_accepted_parts(&rules, &parts));
-  println!("Part 2: {}", count_accepted_combinations(&rules));
+  println!("Part 2: {}", count_accepted_combinations(&rules, &tracer));
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
-use std::fs;
 use std::collections::{HashMap, VecDeque};
 use regex::Regex;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
  
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum Pulse {
@@ -137,9 +139,7 @@ fn count_presses_till_low_rx(modules: &mut HashMap<String, Box<dyn Module>>, src
   }
   return pulse_map.values().fold(1, |acc, s| acc * s);
 }
-fn main() {
-  let contents = fs::read_to_string("./data/q20.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
   let lines = contents.split('\n').collect::<Vec<&str>>();
 
   let mut modules: HashMap<String, Box<dyn Module>> = HashMap::new();
@@ -218,6 +218,12 @@ fn main() {
     }
   }
 
-  println!("Part 1: {}", press_and_count_pulses(&mut modules, 1000));
-  println!("Part 2: {}", count_presses_till_low_rx(&mut modules2, &writes_to_rx_srcs));
+  return Ok(vec![
+    press_and_count_pulses(&mut modules, 1000).into(),
+    count_presses_till_low_rx(&mut modules2, &writes_to_rx_srcs).into()
+  ]);
+}
+
+fn main() {
+  runner::run("q20", solve);
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
-use std::fs;
 use std::collections::HashSet;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 const DIRS: &[(isize, isize)]= &[
   (-1, 0),
@@ -97,13 +99,17 @@ fn count_reachable_squares_big(grid: &Vec<Vec<char>>, start: (usize, usize)) ->
   return total;
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q21.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
 
   let mut grid = contents.split('\n').map(|row| row.chars().collect()).collect::<Vec<Vec<char>>>();
   let start = find_start(&mut grid);
   
-  println!("Part 1: {}", count_reachable_squares_after_steps(&grid, start, 64));
-  println!("Part 2: {}", count_reachable_squares_big(&grid, start));
+  return Ok(vec![
+    count_reachable_squares_after_steps(&grid, start, 64).into(),
+    count_reachable_squares_big(&grid, start).into()
+  ]);
+}
+
+fn main() {
+  runner::run("q21", solve);
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
-use std::{fs, ops::{Sub, Add}};
+use std::ops::{Sub, Add};
 use regex::Regex;
 use num_bigint::{ToBigInt, BigInt};
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 const LOWER: f64 = 200000000000000.0;
 const UPPER: f64 = 400000000000000.0;
@@ -137,9 +140,7 @@ fn sum_initial_rock_pos(hailstones: &Vec<Hailstone>) -> BigInt {
   return &p_rock.x + &p_rock.y + &p_rock.z;
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q24.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, _ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
   let lines = contents.split('\n').collect::<Vec<&str>>();
   let re = Regex::new(r"([\d]+), +([\d]+), +([\d]+) +@ +(-?[\d]+), +(-?[\d]+), +(-?[\d]+)").unwrap();
   let mut hailstones = Vec::new();
@@ -152,6 +153,9 @@ fn main() {
     hailstones.push(Hailstone::new(x, y, z, dx, dy, dz));
   }
 
-  println!("Part 1: {}", count_intersecting(&hailstones));
-  println!("Part 2: {}", sum_initial_rock_pos(&hailstones));
+  return Ok(vec![count_intersecting(&hailstones).into(), sum_initial_rock_pos(&hailstones).into()]);
+}
+
+fn main() {
+  runner::run("q24", solve);
 }
\ No newline at end of file
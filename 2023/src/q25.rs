@@ -1,6 +1,8 @@
-use std::fs;
 use std::collections::HashMap;
 use rand::random_range;
+use aoc_common::answer::Answer;
+use aoc_common::progress::{Cancelled, Context};
+use aoc_common::runner;
 
 #[derive(Clone, Debug)]
 struct SuperNode {
@@ -81,18 +83,19 @@ fn initialise_karger(adj_list: &HashMap<String, Vec<String>>) -> (HashMap<String
   return (supernodes, superedges);
 }
 
-fn multiply_group_sizes(adj_list: &HashMap<String, Vec<String>>) -> u32 {
+fn multiply_group_sizes(adj_list: &HashMap<String, Vec<String>>, ctx: &Context) -> Result<u32, Cancelled> {
   let (supernodes, superedges) = initialise_karger(adj_list);
   let (mut res_nodes, mut res_edges) = karger(supernodes.clone(), superedges.clone());
+  let mut trials: u64 = 1;
   while res_edges.len() > 3 {
+    ctx.progress("karger trials", trials, None)?;
     (res_nodes, res_edges) = karger(supernodes.clone(),superedges.clone());
+    trials += 1;
   }
-  return res_nodes.values().fold(1, |acc, s| acc * s.nodes.len() as u32);
+  return Ok(res_nodes.values().fold(1, |acc, s| acc * s.nodes.len() as u32));
 }
 
-fn main() {
-  let contents = fs::read_to_string("./data/q25.txt")
-    .expect("Should have been able to read file");
+fn solve(contents: &str, ctx: &Context) -> Result<Vec<Answer>, Cancelled> {
   let connections = contents.split('\n').map(|line| {
     let parts = line.split(':').collect::<Vec<&str>>();
     let connections = parts[1].trim().split(' ').map(|c| c.to_string()).collect::<Vec<String>>();
@@ -114,5 +117,9 @@ fn main() {
     }
   }
 
-  println!("Part 1: {}", multiply_group_sizes(&adj_list));
+  return Ok(vec![multiply_group_sizes(&adj_list, ctx)?.into()]);
+}
+
+fn main() {
+  runner::run("q25", solve);
 }
\ No newline at end of file
@@ -0,0 +1,155 @@
+use std::fmt;
+use num_bigint::BigInt;
+
+/*
+  A puzzle answer of any shape. Integers of every width are widened into
+  `Int`, and a `BigInt` that fits is collapsed into `Int` too, so two answers
+  compare equal whatever type the solver happened to return them as.
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Answer {
+  Int(i128),
+  BigInt(BigInt),
+  Str(String),
+  Glyphs(Vec<String>)
+}
+
+impl Answer {
+  pub fn glyphs<R: AsRef<str>>(rows: impl IntoIterator<Item = R>) -> Answer {
+    return Answer::Glyphs(rows.into_iter().map(|row| row.as_ref().trim_end().to_string()).collect());
+  }
+
+  pub fn from_pixels(pixels: &[Vec<bool>]) -> Answer {
+    return Answer::Glyphs(pixels.iter()
+      .map(|row| row.iter().map(|on| if *on { '#' } else { '.' }).collect())
+      .collect());
+  }
+
+  pub fn is_multiline(&self) -> bool {
+    return matches!(self, Answer::Glyphs(_));
+  }
+
+  pub fn as_i128(&self) -> Option<i128> {
+    return match self {
+      Answer::Int(val) => Some(*val),
+      _ => None
+    };
+  }
+
+  pub fn as_u64(&self) -> Option<u64> {
+    return self.as_i128().and_then(|val| u64::try_from(val).ok());
+  }
+
+  pub fn to_json(&self) -> String {
+    return match self {
+      Answer::Int(val) => val.to_string(),
+      Answer::BigInt(val) => val.to_string(),
+      Answer::Str(val) => json_string(val),
+      Answer::Glyphs(rows) => format!("[{}]", rows.iter().map(|row| json_string(row))
+        .collect::<Vec<String>>().join(","))
+    };
+  }
+}
+
+pub fn json_string(val: &str) -> String {
+  let mut out = String::from("\"");
+  for c in val.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c)
+    }
+  }
+  out.push('"');
+  return out;
+}
+
+impl fmt::Display for Answer {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Answer::Int(val) => write!(f, "{}", val),
+      Answer::BigInt(val) => write!(f, "{}", val),
+      Answer::Str(val) => write!(f, "{}", val),
+      Answer::Glyphs(rows) => write!(f, "{}", rows.join("\n"))
+    }
+  }
+}
+
+/*
+  Compares against an answer as it was written down, e.g. in an `.expected`
+  file. Surrounding whitespace is ignored, as are blank lines around glyphs.
+*/
+impl PartialEq<str> for Answer {
+  fn eq(&self, stored: &str) -> bool {
+    let stored = stored.trim();
+    return match self {
+      Answer::Int(val) => stored.parse::<i128>().is_ok_and(|stored| stored == *val),
+      Answer::BigInt(val) => stored.parse::<BigInt>().is_ok_and(|stored| stored == *val),
+      Answer::Str(val) => val.trim() == stored,
+      Answer::Glyphs(rows) => {
+        let stored_rows = stored.lines().map(|row| row.trim()).collect::<Vec<&str>>();
+        rows.len() == stored_rows.len() && rows.iter().zip(stored_rows.iter()).all(|(row, stored)| row.trim() == *stored)
+      }
+    };
+  }
+}
+
+impl PartialEq<&str> for Answer {
+  fn eq(&self, stored: &&str) -> bool {
+    return *self == **stored;
+  }
+}
+
+macro_rules! impl_from_int {
+  ($($t:ty),*) => {
+    $(
+      impl From<$t> for Answer {
+        fn from(val: $t) -> Answer {
+          return Answer::Int(val as i128);
+        }
+      }
+    )*
+  };
+}
+
+impl_from_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, i128);
+
+impl From<u128> for Answer {
+  fn from(val: u128) -> Answer {
+    return match i128::try_from(val) {
+      Ok(val) => Answer::Int(val),
+      Err(_) => Answer::BigInt(BigInt::from(val))
+    };
+  }
+}
+
+impl From<BigInt> for Answer {
+  fn from(val: BigInt) -> Answer {
+    return match i128::try_from(&val) {
+      Ok(val) => Answer::Int(val),
+      Err(_) => Answer::BigInt(val)
+    };
+  }
+}
+
+impl From<String> for Answer {
+  fn from(val: String) -> Answer {
+    return Answer::Str(val);
+  }
+}
+
+impl From<&str> for Answer {
+  fn from(val: &str) -> Answer {
+    return Answer::Str(val.to_string());
+  }
+}
+
+impl From<char> for Answer {
+  fn from(val: char) -> Answer {
+    return Answer::Str(val.to_string());
+  }
+}
@@ -0,0 +1,48 @@
+use std::env;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+  raw: Vec<String>
+}
+
+impl Args {
+  pub fn parse() -> Args {
+    return Args{raw: env::args().skip(1).collect()};
+  }
+
+  pub fn from_vec(raw: Vec<String>) -> Args {
+    return Args{raw};
+  }
+
+  pub fn flag(&self, name: &str) -> bool {
+    return self.raw.iter().any(|arg| arg == name);
+  }
+
+  pub fn value(&self, name: &str) -> Option<&str> {
+    let index = self.raw.iter().position(|arg| arg == name)?;
+    return self.raw.get(index + 1).map(|value| value.as_str());
+  }
+
+  pub fn values(&self, name: &str) -> Vec<&str> {
+    return self.raw.iter().enumerate()
+      .filter(|(_, arg)| *arg == name)
+      .filter_map(|(i, _)| self.raw.get(i + 1).map(|value| value.as_str()))
+      .collect();
+  }
+
+  pub fn time_limit(&self) -> Result<Option<Duration>, String> {
+    let secs = match self.value("--time-limit") {
+      Some(secs) => secs,
+      None => return Ok(None)
+    };
+    return secs.parse::<f64>().ok()
+      .and_then(|limit| Duration::try_from_secs_f64(limit).ok())
+      .map(Some)
+      .ok_or_else(|| format!("--time-limit expects a finite, non-negative number of seconds, got {}", secs));
+  }
+
+  pub fn progress(&self) -> bool {
+    return self.flag("--progress");
+  }
+}
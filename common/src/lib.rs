@@ -0,0 +1,8 @@
+#![allow(clippy::needless_return)]
+
+pub mod answer;
+pub mod cli;
+pub mod progress;
+pub mod runner;
+pub mod trace;
+pub mod vm;
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::cli::Args;
+
+const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cancelled {
+  TimeLimit(Duration),
+  Requested
+}
+
+impl fmt::Display for Cancelled {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Cancelled::TimeLimit(limit) => write!(f, "timed out after {:.1}s", limit.as_secs_f64()),
+      Cancelled::Requested => write!(f, "cancelled")
+    }
+  }
+}
+
+impl std::error::Error for Cancelled {}
+
+/*
+  Shared between a solver and anything that wants to stop it. Solvers call
+  `progress` (or `check` when they have no sensible total) from their hot loop;
+  both return `Err(Cancelled)` once the time limit passes or `cancel` is called,
+  and the solver is expected to unwind with `?`.
+*/
+#[derive(Debug)]
+pub struct Context {
+  start: Instant,
+  time_limit: Option<Duration>,
+  report_interval: Option<Duration>,
+  last_report_ms: AtomicU64,
+  cancelled: Arc<AtomicBool>
+}
+
+impl Context {
+  pub fn new() -> Context {
+    return Context{
+      start: Instant::now(),
+      time_limit: None,
+      report_interval: None,
+      last_report_ms: AtomicU64::new(0),
+      cancelled: Arc::new(AtomicBool::new(false))
+    };
+  }
+
+  pub fn from_args(args: &Args) -> Result<Context, String> {
+    let mut ctx = Context::new();
+    ctx.time_limit = args.time_limit()?;
+    if args.progress() { ctx.report_interval = Some(DEFAULT_REPORT_INTERVAL); }
+    return Ok(ctx);
+  }
+
+  pub fn with_time_limit(mut self, limit: Duration) -> Context {
+    self.time_limit = Some(limit);
+    return self;
+  }
+
+  pub fn with_report_interval(mut self, interval: Duration) -> Context {
+    self.report_interval = Some(interval);
+    return self;
+  }
+
+  pub fn elapsed(&self) -> Duration {
+    return self.start.elapsed();
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+    return self.cancelled.clone();
+  }
+
+  pub fn check(&self) -> Result<(), Cancelled> {
+    if let Some(limit) = self.time_limit {
+      if self.elapsed() > limit {
+        self.cancel();
+        return Err(Cancelled::TimeLimit(limit));
+      }
+    }
+    if self.cancelled.load(Ordering::Relaxed) { return Err(Cancelled::Requested); }
+    return Ok(());
+  }
+
+  pub fn progress(&self, label: &str, done: u64, total: Option<u64>) -> Result<(), Cancelled> {
+    self.check()?;
+    let interval = match self.report_interval {
+      Some(interval) => interval,
+      None => return Ok(())
+    };
+
+    let now_ms = self.elapsed().as_millis() as u64;
+    let last_ms = self.last_report_ms.load(Ordering::Relaxed);
+    if now_ms < last_ms + interval.as_millis() as u64 { return Ok(()); }
+    if self.last_report_ms.compare_exchange(last_ms, now_ms, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+      return Ok(());
+    }
+
+    match total {
+      Some(total) if total > 0 => eprintln!("[{:>7.1}s] {}: {}/{} ({:.1}%)", now_ms as f64 / 1000.0, label,
+        done, total, done as f64 * 100.0 / total as f64),
+      _ => eprintln!("[{:>7.1}s] {}: {}", now_ms as f64 / 1000.0, label, done)
+    }
+    return Ok(());
+  }
+}
+
+impl Default for Context {
+  fn default() -> Context {
+    return Context::new();
+  }
+}
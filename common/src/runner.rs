@@ -0,0 +1,228 @@
+use std::fs;
+use std::panic::{self, RefUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+use crate::answer::{json_string, Answer};
+use crate::cli::Args;
+use crate::progress::{Cancelled, Context};
+
+const SLOW_FACTOR: f64 = 5.0;
+
+enum Outcome {
+  Solved(Vec<Answer>),
+  TimedOut(Cancelled),
+  Panicked(String)
+}
+
+struct Run {
+  input: PathBuf,
+  outcome: Outcome,
+  elapsed: Duration,
+  expected: Option<Vec<String>>
+}
+
+impl Run {
+  fn is_solved(&self) -> bool {
+    return matches!(self.outcome, Outcome::Solved(_));
+  }
+
+  fn mismatches(&self) -> Vec<usize> {
+    let (answers, expected) = match (&self.outcome, &self.expected) {
+      (Outcome::Solved(answers), Some(expected)) => (answers, expected),
+      _ => return Vec::new()
+    };
+    return answers.iter().enumerate()
+      .filter(|(i, answer)| expected.get(*i).is_some_and(|stored| **answer != *stored.as_str()))
+      .map(|(i, _)| i + 1)
+      .collect();
+  }
+
+  fn to_json(&self) -> String {
+    let mut fields = vec![
+      format!("\"input\":{}", json_string(&self.input.display().to_string())),
+      format!("\"elapsed_ms\":{:.3}", self.elapsed.as_secs_f64() * 1000.0)
+    ];
+    match &self.outcome {
+      Outcome::Solved(answers) => {
+        fields.push(String::from("\"status\":\"solved\""));
+        fields.push(format!("\"answers\":[{}]", answers.iter().map(|answer| answer.to_json())
+          .collect::<Vec<String>>().join(",")));
+        if self.expected.is_some() {
+          fields.push(format!("\"mismatches\":{:?}", self.mismatches()));
+        }
+      },
+      Outcome::TimedOut(cancelled) => {
+        fields.push(String::from("\"status\":\"timed_out\""));
+        fields.push(format!("\"error\":{}", json_string(&cancelled.to_string())));
+      },
+      Outcome::Panicked(msg) => {
+        fields.push(String::from("\"status\":\"panicked\""));
+        fields.push(format!("\"error\":{}", json_string(msg)));
+      }
+    }
+    return format!("{{{}}}", fields.join(","));
+  }
+}
+
+fn input_paths(args: &Args, day: &str) -> Vec<PathBuf> {
+  let dir = match args.value("--inputs") {
+    Some(dir) => dir,
+    None => return vec![PathBuf::from(format!("./data/{}.txt", day))]
+  };
+
+  let mut paths = fs::read_dir(dir)
+    .unwrap_or_else(|e| panic!("Should have been able to read directory {}: {}", dir, e))
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+    .collect::<Vec<PathBuf>>();
+  paths.sort();
+  if paths.is_empty() { panic!("No .txt inputs found in {}", dir); }
+  return paths;
+}
+
+/*
+  Expected answers live next to the input as `<name>.expected`, written the
+  same way the runner prints them: `Part N: <answer>`, with glyph answers on
+  the lines following an empty `Part N:`.
+*/
+fn read_expected(input: &Path) -> Option<Vec<String>> {
+  let contents = fs::read_to_string(input.with_extension("expected")).ok()?;
+  let mut expected: Vec<String> = Vec::new();
+  for line in contents.lines() {
+    match line.strip_prefix("Part ").and_then(|rest| rest.split_once(':')) {
+      Some((_, answer)) => expected.push(answer.trim().to_string()),
+      None => if let Some(last) = expected.last_mut() {
+        if !last.is_empty() { last.push('\n'); }
+        last.push_str(line);
+      }
+    }
+  }
+  return Some(expected);
+}
+
+/* Bad options are reported once, before any input is solved. */
+fn context(args: &Args) -> Context {
+  return Context::from_args(args).unwrap_or_else(|message| {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+  });
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+  if let Some(msg) = payload.downcast_ref::<&str>() { return msg.to_string(); }
+  if let Some(msg) = payload.downcast_ref::<String>() { return msg.clone(); }
+  return String::from("unknown panic");
+}
+
+fn run_input<F>(args: &Args, input: &Path, solve: &F) -> Run
+  where F: Fn(&str, &Context) -> Result<Vec<Answer>, Cancelled> + RefUnwindSafe
+{
+  let contents = fs::read_to_string(input)
+    .unwrap_or_else(|e| panic!("Should have been able to read file {}: {}", input.display(), e));
+  let ctx = context(args);
+  let start = Instant::now();
+  let outcome = match panic::catch_unwind(|| solve(&contents, &ctx)) {
+    Ok(Ok(answers)) => Outcome::Solved(answers),
+    Ok(Err(cancelled)) => Outcome::TimedOut(cancelled),
+    Err(payload) => Outcome::Panicked(panic_message(payload))
+  };
+  let expected = if args.flag("--verify") { read_expected(input) } else { None };
+  return Run{input: input.to_path_buf(), outcome, elapsed: start.elapsed(), expected};
+}
+
+fn print_answers(answers: &[Answer], indent: &str) {
+  for (i, answer) in answers.iter().enumerate() {
+    if answer.is_multiline() {
+      println!("{}Part {}:", indent, i + 1);
+      for row in answer.to_string().lines() {
+        println!("{}{}", indent, row);
+      }
+    } else {
+      println!("{}Part {}: {}", indent, i + 1, answer);
+    }
+  }
+}
+
+fn median(mut durations: Vec<Duration>) -> Option<Duration> {
+  if durations.is_empty() { return None; }
+  durations.sort();
+  return Some(durations[durations.len() / 2]);
+}
+
+fn report(runs: &[Run]) -> bool {
+  let typical = median(runs.iter().filter(|run| run.is_solved()).map(|run| run.elapsed).collect());
+  let mut all_ok = true;
+
+  for run in runs.iter() {
+    println!("{} ({:.3}s)", run.input.display(), run.elapsed.as_secs_f64());
+    match &run.outcome {
+      Outcome::Solved(answers) => {
+        print_answers(answers, "  ");
+        if let Some(typical) = typical {
+          if run.elapsed.as_secs_f64() > typical.as_secs_f64() * SLOW_FACTOR {
+            println!("  SLOW: {:.1}x the median of {:.3}s", run.elapsed.as_secs_f64() / typical.as_secs_f64(),
+              typical.as_secs_f64());
+          }
+        }
+      },
+      Outcome::TimedOut(cancelled) => {
+        all_ok = false;
+        println!("  FAILED: {}", cancelled);
+      },
+      Outcome::Panicked(msg) => {
+        all_ok = false;
+        println!("  PANICKED: {}", msg);
+      }
+    }
+
+    let mismatches = run.mismatches();
+    if !mismatches.is_empty() {
+      all_ok = false;
+      println!("  MISMATCH: part(s) {:?} differ from {}", mismatches, run.input.with_extension("expected").display());
+    }
+  }
+
+  let solved = runs.iter().filter(|run| run.is_solved()).count();
+  println!("{}/{} inputs solved", solved, runs.len());
+  return all_ok;
+}
+
+/*
+  Entry point for days that take their whole input as a string. With no
+  arguments this behaves like the old `main`: read `./data/<day>.txt` and print
+  each part. `--inputs <dir>` runs every `*.txt` in the directory instead, each
+  with its own time budget, and flags panics and unusually slow inputs.
+  `--verify` checks answers against `<input>.expected` and `--json` prints one
+  JSON object per input instead of the text report.
+*/
+pub fn run<F>(day: &str, solve: F)
+  where F: Fn(&str, &Context) -> Result<Vec<Answer>, Cancelled> + RefUnwindSafe
+{
+  let args = Args::parse();
+  let paths = input_paths(&args, day);
+  let json = args.flag("--json");
+
+  if args.value("--inputs").is_none() && !json && !args.flag("--verify") {
+    let contents = fs::read_to_string(&paths[0])
+      .expect("Should have been able to read file");
+    match solve(&contents, &context(&args)) {
+      Ok(answers) => print_answers(&answers, ""),
+      Err(cancelled) => {
+        eprintln!("Error: {}", cancelled);
+        process::exit(1);
+      }
+    }
+    return;
+  }
+
+  let runs = paths.iter().map(|path| run_input(&args, path, &solve)).collect::<Vec<Run>>();
+  let all_ok = if json {
+    for run in runs.iter() { println!("{}", run.to_json()); }
+    runs.iter().all(|run| run.is_solved() && run.mismatches().is_empty())
+  } else {
+    report(&runs)
+  };
+  if !all_ok { process::exit(1); }
+}
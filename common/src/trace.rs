@@ -0,0 +1,140 @@
+#[cfg(feature = "trace")]
+use std::cell::Cell;
+#[cfg(feature = "trace")]
+use std::fmt;
+
+use crate::cli::Args;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+  Node,
+  MemoHit,
+  Prune,
+  Leaf
+}
+
+#[cfg(feature = "trace")]
+impl Event {
+  const ALL: [Event; 4] = [Event::Node, Event::MemoHit, Event::Prune, Event::Leaf];
+
+  fn label(&self) -> &'static str {
+    return match self {
+      Event::Node => "node",
+      Event::MemoHit => "memo hit",
+      Event::Prune => "prune",
+      Event::Leaf => "leaf"
+    };
+  }
+}
+
+/*
+  Records search decisions when the crate is built with the `trace` feature and
+  the binary is run with `--trace`. Without the feature this is a zero-sized
+  type and the `trace_*!` macros expand to nothing, so solvers can keep their
+  trace calls in place at no cost.
+*/
+#[derive(Debug, Default)]
+pub struct Tracer {
+  #[cfg(feature = "trace")]
+  enabled: bool,
+  #[cfg(feature = "trace")]
+  counts: [Cell<u64>; 4],
+  #[cfg(feature = "trace")]
+  max_depth: Cell<usize>
+}
+
+impl Tracer {
+  pub fn disabled() -> Tracer {
+    return Tracer::default();
+  }
+
+  #[cfg(feature = "trace")]
+  pub fn from_args(args: &Args) -> Tracer {
+    return Tracer{enabled: args.flag("--trace"), ..Tracer::default()};
+  }
+
+  #[cfg(not(feature = "trace"))]
+  pub fn from_args(args: &Args) -> Tracer {
+    if args.flag("--trace") { eprintln!("--trace ignored: rebuild with `--features trace` to enable it"); }
+    return Tracer::default();
+  }
+
+  #[cfg(feature = "trace")]
+  #[inline]
+  pub fn is_enabled(&self) -> bool {
+    return self.enabled;
+  }
+
+  #[cfg(not(feature = "trace"))]
+  #[inline(always)]
+  pub fn is_enabled(&self) -> bool {
+    return false;
+  }
+
+  #[cfg(feature = "trace")]
+  pub fn record(&self, event: Event, depth: usize, detail: fmt::Arguments) {
+    let index = Event::ALL.iter().position(|e| *e == event).unwrap();
+    self.counts[index].set(self.counts[index].get() + 1);
+    if depth > self.max_depth.get() { self.max_depth.set(depth); }
+    eprintln!("{:indent$}[{}] depth={} {}", "", event.label(), depth, detail, indent = depth * 2);
+  }
+
+  #[cfg(feature = "trace")]
+  pub fn summary(&self, label: &str) {
+    if !self.enabled { return; }
+    let counts = Event::ALL.iter().enumerate()
+      .map(|(i, event)| format!("{}s={}", event.label().replace(' ', "_"), self.counts[i].get()))
+      .collect::<Vec<String>>();
+    eprintln!("[trace] {}: {} max_depth={}", label, counts.join(" "), self.max_depth.get());
+    for count in self.counts.iter() { count.set(0); }
+    self.max_depth.set(0);
+  }
+
+  #[cfg(not(feature = "trace"))]
+  #[inline(always)]
+  pub fn summary(&self, _label: &str) {}
+}
+
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! trace_event {
+  ($tracer:expr, $event:expr, $depth:expr, $($arg:tt)*) => {
+    if $tracer.is_enabled() { $tracer.record($event, $depth as usize, format_args!($($arg)*)); }
+  };
+}
+
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! trace_event {
+  ($tracer:expr, $event:expr, $depth:expr, $($arg:tt)*) => {
+    { let _ = (&$tracer, &$depth); }
+  };
+}
+
+#[macro_export]
+macro_rules! trace_node {
+  ($tracer:expr, $depth:expr, $($arg:tt)*) => {
+    $crate::trace_event!($tracer, $crate::trace::Event::Node, $depth, $($arg)*)
+  };
+}
+
+#[macro_export]
+macro_rules! trace_memo_hit {
+  ($tracer:expr, $depth:expr, $($arg:tt)*) => {
+    $crate::trace_event!($tracer, $crate::trace::Event::MemoHit, $depth, $($arg)*)
+  };
+}
+
+#[macro_export]
+macro_rules! trace_prune {
+  ($tracer:expr, $depth:expr, $($arg:tt)*) => {
+    $crate::trace_event!($tracer, $crate::trace::Event::Prune, $depth, $($arg)*)
+  };
+}
+
+#[macro_export]
+macro_rules! trace_leaf {
+  ($tracer:expr, $depth:expr, $($arg:tt)*) => {
+    $crate::trace_event!($tracer, $crate::trace::Event::Leaf, $depth, $($arg)*)
+  };
+}
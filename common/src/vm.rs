@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+pub type Word = i64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Reg(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+  Next,
+  Jump(Word),
+  Halt
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exit {
+  Halted,
+  OutOfBounds(Word),
+  Stopped
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+  pub registers: Vec<Word>,
+  pub pc: usize,
+  pub cycle: u64,
+  pub executed: u64,
+  pub input: VecDeque<Word>
+}
+
+impl State {
+  pub fn new(number_of_registers: usize) -> State {
+    return State{
+      registers: vec![0; number_of_registers],
+      pc: 0,
+      cycle: 0,
+      executed: 0,
+      input: VecDeque::new()
+    };
+  }
+
+  pub fn get(&self, reg: Reg) -> Word {
+    return self.registers[reg.0];
+  }
+
+  pub fn set(&mut self, reg: Reg, value: Word) {
+    self.registers[reg.0] = value;
+  }
+
+  pub fn read_input(&mut self) -> Option<Word> {
+    return self.input.pop_front();
+  }
+}
+
+/*
+  One instruction of a pluggable instruction set. `execute` applies the
+  instruction's effect once all of its `cycles` have elapsed and says where the
+  program counter goes next; jumps are relative to the current instruction.
+*/
+pub trait Instruction {
+  fn cycles(&self) -> u64 {
+    return 1;
+  }
+
+  fn execute(&self, state: &mut State) -> Flow;
+}
+
+/*
+  Hooks observe (and may stop) execution. `on_cycle` runs at the start of every
+  clock cycle, before the current instruction has taken effect, which is when
+  puzzles like 2022 q10 sample their registers.
+*/
+pub trait Hook<I> {
+  fn on_cycle(&mut self, _state: &State) {}
+
+  fn before_execute(&mut self, _state: &State, _instr: &I) -> bool {
+    return true;
+  }
+
+  fn after_execute(&mut self, _state: &State, _instr: &I) {}
+}
+
+pub struct NoHook;
+
+impl<I> Hook<I> for NoHook {}
+
+pub struct Machine<'a, I> {
+  pub program: &'a [I],
+  pub state: State
+}
+
+impl<'a, I: Instruction> Machine<'a, I> {
+  pub fn new(program: &'a [I], number_of_registers: usize) -> Machine<'a, I> {
+    return Machine{program, state: State::new(number_of_registers)};
+  }
+
+  pub fn with_registers(program: &'a [I], registers: Vec<Word>) -> Machine<'a, I> {
+    let mut state = State::new(registers.len());
+    state.registers = registers;
+    return Machine{program, state};
+  }
+
+  pub fn with_input(mut self, input: impl IntoIterator<Item = Word>) -> Machine<'a, I> {
+    self.state.input.extend(input);
+    return self;
+  }
+
+  pub fn step<H: Hook<I>>(&mut self, hook: &mut H) -> Option<Exit> {
+    let instr = match self.program.get(self.state.pc) {
+      Some(instr) => instr,
+      None => return Some(Exit::OutOfBounds(self.state.pc as Word))
+    };
+    if !hook.before_execute(&self.state, instr) { return Some(Exit::Stopped); }
+
+    for _ in 0..instr.cycles() {
+      self.state.cycle += 1;
+      hook.on_cycle(&self.state);
+    }
+
+    let flow = instr.execute(&mut self.state);
+    self.state.executed += 1;
+    hook.after_execute(&self.state, instr);
+
+    let next_pc = match flow {
+      Flow::Next => self.state.pc as Word + 1,
+      Flow::Jump(offset) => self.state.pc as Word + offset,
+      Flow::Halt => return Some(Exit::Halted)
+    };
+    if next_pc < 0 || next_pc as usize >= self.program.len() { return Some(Exit::OutOfBounds(next_pc)); }
+    self.state.pc = next_pc as usize;
+    return None;
+  }
+
+  pub fn run_with<H: Hook<I>>(&mut self, hook: &mut H) -> Exit {
+    loop {
+      if let Some(exit) = self.step(hook) { return exit; }
+    }
+  }
+
+  pub fn run(&mut self) -> Exit {
+    return self.run_with(&mut NoHook);
+  }
+}